@@ -9,7 +9,7 @@ use bevy::{
     window::{CursorGrabMode, CursorOptions},
 };
 
-use bevy_fps_controller::controller::*;
+use bevy_fps_controller::controller::{avian::*, *};
 
 const SPAWN_POINT: Vec3 = Vec3::new(0.0, 1.625, 0.0);
 
@@ -22,7 +22,7 @@ fn main() {
         .insert_resource(ClearColor(Color::linear_rgb(0.83, 0.96, 0.96)))
         .add_plugins(DefaultPlugins)
         .add_plugins(PhysicsPlugins::default())
-        .add_plugins(FpsControllerPlugin)
+        .add_plugins(FpsControllerPlugin::default())
         .add_systems(Startup, setup)
         .add_systems(
             Update,
@@ -67,6 +67,8 @@ fn setup(mut commands: Commands, mut window: Query<&mut Window>, assets: Res<Ass
                 combine_rule: CoefficientCombine::Min,
             },
             LinearVelocity::ZERO,
+            // For kinematic move and slide instead of relying on the physics solver,
+            // use `RigidBody::Kinematic` and set `collision_mode: CollisionMode::Kinematic`
             RigidBody::Dynamic,
             LockedAxes::ROTATION_LOCKED,
             Mass(1.0),
@@ -179,20 +181,21 @@ fn manage_cursor(
     btn: Res<ButtonInput<MouseButton>>,
     key: Res<ButtonInput<KeyCode>>,
     mut cursor: Single<&mut CursorOptions>,
-    mut controller_query: Query<&mut FpsController>,
+    mut commands: Commands,
+    controller_query: Query<Entity, With<FpsController>>,
 ) {
     if btn.just_pressed(MouseButton::Left) {
         cursor.grab_mode = CursorGrabMode::Locked;
         cursor.visible = false;
-        for mut controller in &mut controller_query {
-            controller.enable_input = true;
+        for entity in &controller_query {
+            commands.entity(entity).insert(FpsInputEnabled(true));
         }
     }
     if key.just_pressed(KeyCode::Escape) {
         cursor.grab_mode = CursorGrabMode::None;
         cursor.visible = true;
-        for mut controller in &mut controller_query {
-            controller.enable_input = false;
+        for entity in &controller_query {
+            commands.entity(entity).insert(FpsInputEnabled(false));
         }
     }
 }
@@ -9,7 +9,7 @@ use bevy::{
 };
 use bevy_rapier3d::prelude::*;
 
-use bevy_fps_controller::controller::*;
+use bevy_fps_controller::controller::{rapier::*, *};
 
 const SPAWN_POINT: Vec3 = Vec3::new(0.0, 1.625, 0.0);
 
@@ -22,7 +22,7 @@ fn main() {
         .insert_resource(ClearColor(Color::linear_rgb(0.83, 0.96, 0.96)))
         .add_plugins(DefaultPlugins)
         .add_plugins(RapierPhysicsPlugin::<NoUserData>::default())
-        .add_plugins(FpsControllerPlugin)
+        .add_plugins(FpsControllerPlugin::default())
         .add_systems(Startup, setup)
         .add_systems(
             Update,
@@ -67,6 +67,10 @@ fn setup(mut commands: Commands, mut window: Query<&mut Window>, assets: Res<Ass
             },
             ActiveEvents::COLLISION_EVENTS,
             Velocity::zero(),
+            // For kinematic move and slide instead of relying on the physics solver,
+            // use `RigidBody::KinematicPositionBased` and set `collision_mode: CollisionMode::Kinematic`
+            // To use Rapier's character controller with its autostep and snap to ground instead,
+            // also add a `KinematicCharacterController` and set `collision_mode: CollisionMode::CharacterController`
             RigidBody::Dynamic,
             Sleeping::disabled(),
             LockedAxes::ROTATION_LOCKED,
@@ -182,20 +186,21 @@ fn manage_cursor(
     btn: Res<ButtonInput<MouseButton>>,
     key: Res<ButtonInput<KeyCode>>,
     mut cursor: Single<&mut CursorOptions>,
-    mut controller_query: Query<&mut FpsController>,
+    mut commands: Commands,
+    controller_query: Query<Entity, With<FpsController>>,
 ) {
     if btn.just_pressed(MouseButton::Left) {
         cursor.grab_mode = CursorGrabMode::Locked;
         cursor.visible = false;
-        for mut controller in &mut controller_query {
-            controller.enable_input = true;
+        for entity in &controller_query {
+            commands.entity(entity).insert(FpsInputEnabled(true));
         }
     }
     if key.just_pressed(KeyCode::Escape) {
         cursor.grab_mode = CursorGrabMode::None;
         cursor.visible = true;
-        for mut controller in &mut controller_query {
-            controller.enable_input = false;
+        for entity in &controller_query {
+            commands.entity(entity).insert(FpsInputEnabled(false));
         }
     }
 }
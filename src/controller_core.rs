@@ -0,0 +1,2477 @@
+use bevy::{
+    ecs::world::DeferredWorld,
+    ecs::{
+        component::Mutable,
+        lifecycle::HookContext,
+        schedule::{InternedScheduleLabel, ScheduleLabel},
+    },
+    math::Vec3Swizzles,
+    prelude::*,
+};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::controller_input::*;
+
+#[cfg(any(feature = "avian", feature = "rapier"))]
+#[derive(Resource, Default)]
+pub struct DidFixedTimestepRunThisFrame(bool);
+
+#[derive(Clone, Copy, Debug, PartialEq, Reflect)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    reflect(Serialize, Deserialize)
+)]
+pub enum MoveMode {
+    Noclip,
+    Ground,
+    /// Running along a wall while airborne, see [`FpsController::enable_wall_run`]
+    WallRun,
+    /// Crouch sliding along the ground, see [`FpsController::enable_slide`]
+    Slide,
+}
+
+/// How the controller resolves collisions with the world.
+#[derive(Clone, Copy, Debug, PartialEq, Reflect)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    reflect(Serialize, Deserialize)
+)]
+pub enum CollisionMode {
+    /// Velocity is written to a dynamic rigid body and the physics solver handles collisions
+    Dynamic,
+    /// The controller sweeps its own collider and slides along surfaces, like the Source engine.
+    /// The logical player should use a kinematic rigid body.
+    Kinematic,
+    /// A character controller of the physics backend moves the player and resolves collisions,
+    /// for example Rapier's `KinematicCharacterController`.
+    /// Its stepping replaces the step offset of the controller.
+    CharacterController,
+}
+
+#[derive(Component, Reflect, Default)]
+#[reflect(Component, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    reflect(Serialize, Deserialize)
+)]
+pub struct LogicalPlayer;
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    reflect(Serialize, Deserialize)
+)]
+pub struct RenderPlayer {
+    #[entities]
+    pub logical_entity: Entity,
+}
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    reflect(Serialize, Deserialize)
+)]
+pub struct CameraConfig {
+    pub height_offset: f32,
+}
+
+#[derive(Component, Reflect, Clone, Debug, Default, PartialEq)]
+#[require(FpsInputToggles)]
+#[reflect(Component, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    reflect(Serialize, Deserialize)
+)]
+pub struct FpsControllerInput {
+    pub fly: bool,
+    pub sprint: bool,
+    /// Slow movement modifier
+    pub walk: bool,
+    pub jump: bool,
+    pub crouch: bool,
+    pub pitch: f32,
+    pub yaw: f32,
+    pub movement: Vec3,
+}
+
+/// Runtime movement state advanced by [`step_movement`], kept on the entity in [`FpsControllerState`].
+#[derive(Reflect, Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    reflect(Serialize, Deserialize)
+)]
+pub struct MovementState {
+    /// Copied from the transform before each step, which stays the source of truth
+    pub translation: Vec3,
+    /// Velocity in kinematic and character controller collision modes, where the physics engine does not own it
+    pub velocity: Vec3,
+    pub ground_tick: u8,
+    /// Ticks left in which an earlier jump press is still honored
+    pub jump_buffer_tick: u32,
+    /// Ticks left in which a jump is still allowed after leaving the ground
+    pub coyote_tick: u32,
+    /// Jump has been held since the last jump, which blocks jumping again without auto hop
+    pub jump_consumed: bool,
+    /// Still rising from a jump, releasing jump now cuts it short
+    pub jumping: bool,
+    /// Jump was held on a tick after the current jump started, so releasing it is a deliberate cut
+    pub jump_held: bool,
+    /// Jumps made in the air since last touching the ground
+    pub air_jumps_used: u8,
+    /// Ticks spent running on the current wall
+    pub wall_run_tick: u32,
+    /// Horizontal normal of the wall last run on, zero after touching the ground
+    pub wall_normal: Vec3,
+    /// Horizontal normal of the wall last jumped off
+    pub wall_jump_normal: Vec3,
+    /// Ticks left before the wall last jumped off can be jumped off again
+    pub wall_jump_cooldown_tick: u32,
+    /// Ticks left before starting a slide gives a boost again
+    pub slide_boost_cooldown_tick: u32,
+    pub height: f32,
+    pub move_mode: MoveMode,
+}
+
+impl MovementState {
+    /// Returns the state of a controller standing upright on the ground.
+    pub fn new(controller: &FpsController) -> Self {
+        Self {
+            translation: Vec3::ZERO,
+            velocity: Vec3::ZERO,
+            ground_tick: 0,
+            jump_buffer_tick: 0,
+            coyote_tick: 0,
+            jump_consumed: false,
+            jumping: false,
+            jump_held: false,
+            air_jumps_used: 0,
+            wall_run_tick: 0,
+            wall_normal: Vec3::ZERO,
+            wall_jump_normal: Vec3::ZERO,
+            wall_jump_cooldown_tick: 0,
+            slide_boost_cooldown_tick: 0,
+            height: controller.upright_height,
+            move_mode: MoveMode::Ground,
+        }
+    }
+}
+
+/// Movement tunables of a controller.
+/// Runtime state lives in [`FpsControllerState`], which is added for this controller if missing.
+#[derive(Component, Reflect, Clone, Debug)]
+#[require(FpsControllerState)]
+#[component(on_add = init_controller_state)]
+#[reflect(Component, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(default),
+    reflect(Serialize, Deserialize)
+)]
+pub struct FpsController {
+    pub collision_mode: CollisionMode,
+    /// Used when sliding along surfaces in kinematic collision mode,
+    /// values above one push the player slightly away from the surface
+    pub overbounce: f32,
+    pub radius: f32,
+    pub gravity: f32,
+    /// Multiplies gravity while falling, values above one give a snappier platformer arc
+    pub fall_gravity_scale: f32,
+    /// If the distance to the ground is less than this value, the player is considered grounded
+    pub grounded_distance: f32,
+    pub walk_speed: f32,
+    pub run_speed: f32,
+    /// Maximum speed while the walk modifier is held
+    pub slow_walk_speed: f32,
+    pub forward_speed: f32,
+    pub side_speed: f32,
+    pub air_speed_cap: f32,
+    pub air_acceleration: f32,
+    pub max_air_speed: f32,
+    pub acceleration: f32,
+    pub friction: f32,
+    /// If the dot product (alignment) of the normal of the surface and the upward vector,
+    /// which is a value from [-1, 1], is greater than this value, ground movement is applied
+    pub traction_normal_cutoff: f32,
+    pub friction_speed_cutoff: f32,
+    pub jump_speed: f32,
+    /// Seconds a jump press is remembered before landing
+    pub jump_buffer_time: f32,
+    /// Seconds after walking off a ledge in which jumping is still allowed
+    pub coyote_time: f32,
+    /// Keep jumping while jump is held, otherwise each jump needs a fresh press
+    pub auto_hop: bool,
+    /// Multiplies upward velocity when a held jump is released while still rising, one disables variable jump height
+    pub jump_cut_factor: f32,
+    /// Number of extra jumps allowed in the air, each needs a fresh press
+    pub air_jumps: u8,
+    pub air_jump_speed: f32,
+    /// Fraction of the angle between the horizontal velocity and the wish direction that an air jump turns by, from 0 to 1
+    pub air_jump_redirect: f32,
+    pub fly_speed: f32,
+    pub crouched_speed: f32,
+    pub crouch_speed: f32,
+    pub uncrouch_speed: f32,
+    pub upright_height: f32,
+    pub crouch_height: f32,
+    pub fast_fly_speed: f32,
+    pub fly_friction: f32,
+    pub stop_speed: f32,
+    pub crouch_mode: ButtonMode,
+    pub sprint_mode: ButtonMode,
+    pub walk_mode: ButtonMode,
+    /// Maximum height of a step that is instantly cleared, only applies to cylinder colliders
+    pub step_offset: f32,
+    /// Prevent falling off ledges while crouching on the ground
+    pub enable_ledge_cling: bool,
+    /// Distance at which walls are detected by sideways shape casts while airborne
+    pub wall_distance: f32,
+    /// Run along walls when airborne, moving fast and holding toward the wall
+    pub enable_wall_run: bool,
+    /// Minimum horizontal speed to start and keep wall running
+    pub wall_run_min_speed: f32,
+    /// Multiplies gravity while wall running
+    pub wall_run_gravity_scale: f32,
+    /// Seconds a single wall can be run on before falling off
+    pub wall_run_time: f32,
+    /// Jump off walls when pressing jump while airborne next to one
+    pub enable_wall_jump: bool,
+    /// Speed away from the wall given by a wall jump
+    pub wall_jump_outward_speed: f32,
+    pub wall_jump_upward_speed: f32,
+    /// Seconds before the same wall can be jumped off or run on again, so a single wall can not be climbed
+    pub wall_jump_cooldown: f32,
+    /// Slide when crouching on the ground while moving fast
+    pub enable_slide: bool,
+    /// Minimum horizontal speed to start a slide
+    pub slide_start_speed: f32,
+    /// The slide ends when the horizontal speed drops below this
+    pub slide_end_speed: f32,
+    /// Ground friction while sliding, used instead of `friction`
+    pub slide_friction: f32,
+    /// Speed added in the direction of travel when a slide starts
+    pub slide_boost: f32,
+    /// Seconds after a boosted slide before the next slide is boosted again
+    pub slide_boost_cooldown: f32,
+}
+
+impl Default for FpsController {
+    fn default() -> Self {
+        Self {
+            collision_mode: CollisionMode::Dynamic,
+            overbounce: 1.0,
+            grounded_distance: 0.125,
+            radius: 0.5,
+            fly_speed: 10.0,
+            fast_fly_speed: 30.0,
+            gravity: 23.0,
+            fall_gravity_scale: 1.0,
+            walk_speed: 9.0,
+            run_speed: 14.0,
+            slow_walk_speed: 6.0,
+            forward_speed: 30.0,
+            side_speed: 30.0,
+            air_speed_cap: 2.0,
+            air_acceleration: 20.0,
+            max_air_speed: 15.0,
+            crouched_speed: 5.0,
+            crouch_speed: 6.0,
+            uncrouch_speed: 8.0,
+            upright_height: 3.0,
+            crouch_height: 1.5,
+            acceleration: 10.0,
+            friction: 10.0,
+            traction_normal_cutoff: 0.7,
+            friction_speed_cutoff: 0.1,
+            fly_friction: 0.5,
+            stop_speed: 1.0,
+            jump_speed: 8.5,
+            jump_buffer_time: 0.0,
+            coyote_time: 0.0,
+            auto_hop: true,
+            jump_cut_factor: 1.0,
+            air_jumps: 0,
+            air_jump_speed: 8.5,
+            air_jump_redirect: 1.0,
+            step_offset: 0.25,
+            crouch_mode: ButtonMode::Hold,
+            sprint_mode: ButtonMode::Hold,
+            walk_mode: ButtonMode::Hold,
+            enable_ledge_cling: true,
+            wall_distance: 0.25,
+            enable_wall_run: false,
+            wall_run_min_speed: 7.0,
+            wall_run_gravity_scale: 0.25,
+            wall_run_time: 1.5,
+            enable_wall_jump: false,
+            wall_jump_outward_speed: 7.0,
+            wall_jump_upward_speed: 8.5,
+            wall_jump_cooldown: 1.0,
+            enable_slide: false,
+            slide_start_speed: 10.0,
+            slide_end_speed: 5.0,
+            slide_friction: 1.0,
+            slide_boost: 3.0,
+            slide_boost_cooldown: 1.0,
+        }
+    }
+}
+
+/// Mutable runtime state of a controller, the part to copy for networking and save games.
+#[derive(Component, Reflect, Clone, Copy, Debug, PartialEq)]
+#[reflect(Component, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    reflect(Serialize, Deserialize)
+)]
+pub struct FpsControllerState {
+    pub movement: MovementState,
+    pub pitch: f32,
+    pub yaw: f32,
+    /// Move mode after the last tick that messages were sent for
+    pub previous_move_mode: MoveMode,
+    pub previous_translation: Option<Vec3>,
+}
+
+impl Default for FpsControllerState {
+    fn default() -> Self {
+        Self::new(&FpsController::default())
+    }
+}
+
+impl FpsControllerState {
+    /// Returns the state of a controller standing upright on the ground.
+    pub fn new(controller: &FpsController) -> Self {
+        Self {
+            movement: MovementState::new(controller),
+            pitch: 0.0,
+            yaw: 0.0,
+            previous_move_mode: MoveMode::Ground,
+            previous_translation: None,
+        }
+    }
+}
+
+/// The state required by a new controller is created from the defaults,
+/// so fix it up for the tunables of the controller it was added for.
+fn init_controller_state(mut world: DeferredWorld, context: HookContext) {
+    let Some(controller) = world.get::<FpsController>(context.entity).cloned() else {
+        return;
+    };
+    if let Some(mut state) = world.get_mut::<FpsControllerState>(context.entity) {
+        if *state == FpsControllerState::default() {
+            *state = FpsControllerState::new(&controller);
+        }
+    }
+}
+
+/// Sent when a controller starts running along a wall.
+#[derive(Message, Clone, Copy, Debug)]
+pub struct WallRunStarted {
+    pub entity: Entity,
+    pub wall_normal: Vec3,
+}
+
+/// Sent when a controller stops running along a wall, for any reason.
+#[derive(Message, Clone, Copy, Debug)]
+pub struct WallRunEnded {
+    pub entity: Entity,
+}
+
+/// Sent when a controller starts a crouch slide.
+#[derive(Message, Clone, Copy, Debug)]
+pub struct SlideStarted {
+    pub entity: Entity,
+}
+
+/// Sent when a controller stops sliding, for any reason.
+#[derive(Message, Clone, Copy, Debug)]
+pub struct SlideEnded {
+    pub entity: Entity,
+}
+
+//     ____             __                  __
+//    / __ )____ ______/ /_____  ____  ____/ /
+//   / __  / __ `/ ___/ //_/ _ \/ __ \/ __  /
+//  / /_/ / /_/ / /__/ ,< /  __/ / / / /_/ /
+// /_____/\__,_/\___/_/|_|\___/_/ /_/\__,_/
+
+/// Collider shapes supported by the controller, independent of the physics backend.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ControllerShape {
+    Cylinder {
+        radius: f32,
+        half_height: f32,
+    },
+    /// The half height does not include the hemispherical caps
+    Capsule {
+        radius: f32,
+        half_height: f32,
+    },
+}
+
+impl ControllerShape {
+    /// Returns the offset that puts a point at the center of the player transform to the bottom of the collider.
+    /// Needed for when we want to originate something at the foot of the player.
+    pub fn y_offset(&self) -> Vec3 {
+        Vec3::Y
+            * match *self {
+                ControllerShape::Cylinder { half_height, .. } => half_height,
+                ControllerShape::Capsule {
+                    radius,
+                    half_height,
+                } => half_height + radius,
+            }
+    }
+
+    /// Return a shape that is scaled laterally (XZ plane) but not vertically (Y axis).
+    pub fn scaled_laterally(&self, scale: f32) -> Self {
+        match *self {
+            ControllerShape::Cylinder {
+                radius,
+                half_height,
+            } => ControllerShape::Cylinder {
+                radius: radius * scale,
+                half_height,
+            },
+            ControllerShape::Capsule {
+                radius,
+                half_height,
+            } => ControllerShape::Capsule {
+                radius: radius * scale,
+                half_height,
+            },
+        }
+    }
+
+    /// Return a shape with the same radius whose total height is the given value.
+    pub fn with_height(&self, height: f32) -> Self {
+        match *self {
+            ControllerShape::Cylinder { radius, .. } => ControllerShape::Cylinder {
+                radius,
+                half_height: height * 0.5,
+            },
+            ControllerShape::Capsule { radius, .. } => ControllerShape::Capsule {
+                radius,
+                half_height: height * 0.5 - radius,
+            },
+        }
+    }
+}
+
+/// Collider component of a physics backend that can be used by the controller.
+pub trait ControllerCollider: Component<Mutability = Mutable> {
+    fn controller_shape(&self) -> ControllerShape;
+
+    fn set_controller_shape(&mut self, shape: ControllerShape);
+}
+
+/// Velocity component of a physics backend, owned by the physics engine in [`CollisionMode::Dynamic`].
+pub trait ControllerVelocity: Component<Mutability = Mutable> {
+    fn linear_velocity(&self) -> Vec3;
+
+    fn set_linear_velocity(&mut self, velocity: Vec3);
+}
+
+pub struct ShapeHit {
+    pub distance: f32,
+    /// Normal of the hit surface in world space
+    pub normal: Vec3,
+}
+
+/// A single spatial query issued by the movement logic.
+/// Shapes are always cast upright, as the controller does not rotate.
+pub enum SpatialCast {
+    Shape {
+        shape: ControllerShape,
+        origin: Vec3,
+        direction: Dir3,
+        max_distance: f32,
+    },
+    Ray {
+        origin: Vec3,
+        direction: Dir3,
+        max_distance: f32,
+    },
+    /// Expects the deepest penetration, see [`PhysicsQuery::penetration`]
+    Penetration {
+        shape: ControllerShape,
+        origin: Vec3,
+    },
+}
+
+/// Spatial queries the movement logic needs from a physics backend.
+/// Implementations are expected to exclude the controller itself from the results.
+///
+/// Closures of the form `Fn(&SpatialCast) -> Option<ShapeHit>` implement this trait,
+/// which is convenient for headless simulation with [`step_movement`].
+pub trait PhysicsQuery {
+    fn cast_shape(
+        &self,
+        shape: &ControllerShape,
+        origin: Vec3,
+        direction: Dir3,
+        max_distance: f32,
+    ) -> Option<ShapeHit>;
+
+    /// Returns the distance to the first hit, if any
+    fn cast_ray(&self, origin: Vec3, direction: Dir3, max_distance: f32) -> Option<f32>;
+
+    /// Returns the deepest penetration of the shape placed at the origin, if any.
+    /// The distance of the hit is the penetration depth and the normal points out of the penetrated surface.
+    fn penetration(&self, shape: &ControllerShape, origin: Vec3) -> Option<ShapeHit>;
+}
+
+impl<F: Fn(&SpatialCast) -> Option<ShapeHit>> PhysicsQuery for F {
+    fn cast_shape(
+        &self,
+        shape: &ControllerShape,
+        origin: Vec3,
+        direction: Dir3,
+        max_distance: f32,
+    ) -> Option<ShapeHit> {
+        self(&SpatialCast::Shape {
+            shape: *shape,
+            origin,
+            direction,
+            max_distance,
+        })
+    }
+
+    fn cast_ray(&self, origin: Vec3, direction: Dir3, max_distance: f32) -> Option<f32> {
+        self(&SpatialCast::Ray {
+            origin,
+            direction,
+            max_distance,
+        })
+        .map(|hit| hit.distance)
+    }
+
+    fn penetration(&self, shape: &ControllerShape, origin: Vec3) -> Option<ShapeHit> {
+        self(&SpatialCast::Penetration {
+            shape: *shape,
+            origin,
+        })
+    }
+}
+
+/// Phases of the controller, order your own systems against these.
+///
+/// [`Input`](Self::Input), [`Look`](Self::Look) and [`Render`](Self::Render) run in [`RunFixedMainLoop`],
+/// before and after the fixed main loop respectively.
+/// [`Move`](Self::Move) and [`PostMove`](Self::PostMove) run in the movement schedule,
+/// [`FixedPreUpdate`] unless changed with [`FpsControllerPluginSettings::with_movement_schedule`].
+#[derive(SystemSet, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FpsControllerSystems {
+    /// Gathers device input into [`FpsControllerInput`]
+    Input,
+    /// Applies the look direction of [`FpsControllerInput`] to [`FpsControllerState`]
+    Look,
+    /// Runs one tick of movement
+    Move,
+    /// Updates the collider to the new height of the controller and sends movement messages
+    PostMove,
+    /// Clears consumed input and interpolates the camera
+    Render,
+}
+
+/// Settings of the backend `FpsControllerPlugin`.
+///
+/// ```ignore
+/// FpsControllerPlugin {
+///     settings: FpsControllerPluginSettings::default().without_input(),
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct FpsControllerPluginSettings {
+    /// Schedule that [`FpsControllerSystems::Move`] and [`FpsControllerSystems::PostMove`] run in
+    pub movement_schedule: InternedScheduleLabel,
+    /// Adds [`fps_controller_input`], turn off to write [`FpsControllerInput`] yourself
+    pub input: bool,
+    /// Adds [`fps_controller_render`], turn off when using your own camera rig
+    pub render: bool,
+}
+
+impl Default for FpsControllerPluginSettings {
+    fn default() -> Self {
+        Self {
+            movement_schedule: FixedPreUpdate.intern(),
+            input: true,
+            render: true,
+        }
+    }
+}
+
+impl FpsControllerPluginSettings {
+    /// Runs movement in the given schedule instead of [`FixedPreUpdate`].
+    /// In schedules outside the fixed main loop the camera is not interpolated
+    /// and input is cleared after every movement tick.
+    pub fn with_movement_schedule(mut self, schedule: impl ScheduleLabel) -> Self {
+        self.movement_schedule = schedule.intern();
+        self
+    }
+
+    /// Does not add the built-in keyboard and mouse input, so you can write [`FpsControllerInput`] yourself.
+    pub fn without_input(mut self) -> Self {
+        self.input = false;
+        self
+    }
+
+    /// Does not add the built-in camera interpolation, for when you have your own camera rig.
+    pub fn without_render(mut self) -> Self {
+        self.render = false;
+        self
+    }
+
+    /// Whether movement runs in one of the schedules of the fixed main loop.
+    #[cfg(any(feature = "avian", feature = "rapier"))]
+    fn fixed_movement_schedule(&self) -> bool {
+        [
+            FixedFirst.intern(),
+            FixedPreUpdate.intern(),
+            FixedUpdate.intern(),
+            FixedPostUpdate.intern(),
+            FixedLast.intern(),
+        ]
+        .contains(&self.movement_schedule)
+    }
+}
+
+#[cfg(any(feature = "avian", feature = "rapier"))]
+/// Adds the backend-independent systems, `fps_controller_move` is provided by the backend.
+pub(crate) fn add_controller_systems<C: ControllerCollider, M>(
+    app: &mut App,
+    settings: &FpsControllerPluginSettings,
+    fps_controller_move: impl IntoScheduleConfigs<bevy::ecs::system::ScheduleSystem, M>,
+) {
+    let movement_schedule = settings.movement_schedule;
+
+    app.add_message::<WallRunStarted>()
+        .add_message::<WallRunEnded>()
+        .add_message::<SlideStarted>()
+        .add_message::<SlideEnded>()
+        .init_resource::<DefaultFpsInputMap>()
+        .register_type::<FpsController>()
+        .register_type::<FpsControllerState>()
+        .register_type::<FpsControllerInput>()
+        .register_type::<FpsInputMap>()
+        .register_type::<DefaultFpsInputMap>()
+        .register_type::<FpsInputDevice>()
+        .register_type::<FpsGamepadSettings>()
+        .register_type::<FpsInputToggles>()
+        .register_type::<FpsLookSettings>()
+        .register_type::<FpsInputEnabled>()
+        .register_type::<CameraConfig>()
+        .register_type::<LogicalPlayer>()
+        .register_type::<RenderPlayer>()
+        .configure_sets(
+            RunFixedMainLoop,
+            (
+                (FpsControllerSystems::Input, FpsControllerSystems::Look)
+                    .chain()
+                    .in_set(RunFixedMainLoopSystems::BeforeFixedMainLoop),
+                FpsControllerSystems::Render.in_set(RunFixedMainLoopSystems::AfterFixedMainLoop),
+            ),
+        )
+        .configure_sets(
+            movement_schedule,
+            (FpsControllerSystems::Move, FpsControllerSystems::PostMove).chain(),
+        )
+        .add_systems(
+            movement_schedule,
+            (
+                fps_controller_move.in_set(FpsControllerSystems::Move),
+                (
+                    fps_controller_update_collider::<C>,
+                    fps_controller_move_mode_messages,
+                )
+                    .in_set(FpsControllerSystems::PostMove),
+            ),
+        )
+        .add_systems(
+            RunFixedMainLoop,
+            fps_controller_look.in_set(FpsControllerSystems::Look),
+        );
+
+    if settings.fixed_movement_schedule() {
+        // Frames without a fixed tick keep their input for the next one, so presses are not lost
+        app.init_resource::<DidFixedTimestepRunThisFrame>()
+            .add_systems(PreUpdate, clear_fixed_timestep_flag)
+            .add_systems(
+                movement_schedule,
+                set_fixed_time_step_flag.in_set(FpsControllerSystems::Move),
+            )
+            .add_systems(
+                RunFixedMainLoop,
+                clear_input
+                    .run_if(did_fixed_timestep_run_this_frame)
+                    .in_set(FpsControllerSystems::Render),
+            );
+    } else {
+        app.add_systems(
+            movement_schedule,
+            clear_input.after(FpsControllerSystems::PostMove),
+        );
+    }
+
+    #[cfg(feature = "preset")]
+    crate::controller_preset::add_preset_systems(app);
+
+    if settings.input {
+        app.add_systems(
+            RunFixedMainLoop,
+            fps_controller_input.in_set(FpsControllerSystems::Input),
+        );
+    }
+    if settings.render {
+        app.add_systems(
+            RunFixedMainLoop,
+            fps_controller_render::<C>
+                .with_input(settings.fixed_movement_schedule())
+                .in_set(FpsControllerSystems::Render),
+        );
+    }
+}
+
+//     __                _
+//    / /   ____  ____ _(_)____
+//   / /   / __ \/ __ `/ / ___/
+//  / /___/ /_/ / /_/ / / /__
+// /_____/\____/\__, /_/\___/
+//             /____/
+
+const SLIGHT_SCALE_DOWN: f32 = 0.9375;
+
+// Gap kept between the collider and surfaces when sliding so sweeps do not start in contact
+const SKIN_WIDTH: f32 = 0.015625;
+
+const MAX_BUMPS: usize = 4;
+const MAX_CLIP_PLANES: usize = 5;
+const MAX_DEPENETRATION_ITERATIONS: usize = 4;
+
+// Surfaces whose normal points up or down more than this are not considered walls
+const MAX_WALL_NORMAL_Y: f32 = 0.3;
+// Walls with normals aligned more than this are considered the same wall
+const SAME_WALL_ALIGNMENT: f32 = 0.9;
+
+#[cfg(any(feature = "avian", feature = "rapier"))]
+fn clear_fixed_timestep_flag(
+    mut did_fixed_timestep_run_this_frame: ResMut<DidFixedTimestepRunThisFrame>,
+) {
+    did_fixed_timestep_run_this_frame.0 = false;
+}
+
+#[cfg(any(feature = "avian", feature = "rapier"))]
+fn set_fixed_time_step_flag(
+    mut did_fixed_timestep_run_this_frame: ResMut<DidFixedTimestepRunThisFrame>,
+) {
+    did_fixed_timestep_run_this_frame.0 = true;
+}
+
+#[cfg(any(feature = "avian", feature = "rapier"))]
+fn did_fixed_timestep_run_this_frame(
+    did_fixed_timestep_run_this_frame: Res<DidFixedTimestepRunThisFrame>,
+) -> bool {
+    did_fixed_timestep_run_this_frame.0
+}
+
+#[cfg(any(feature = "avian", feature = "rapier"))]
+fn clear_input(mut query: Query<&mut FpsControllerInput>) {
+    for mut input in query.iter_mut() {
+        input.movement = Vec3::ZERO;
+        input.sprint = false;
+        input.walk = false;
+        input.jump = false;
+        input.fly = false;
+        input.crouch = false;
+    }
+}
+
+pub fn fps_controller_look(mut query: Query<(&mut FpsControllerState, &FpsControllerInput)>) {
+    for (mut state, input) in query.iter_mut() {
+        state.pitch = input.pitch;
+        state.yaw = input.yaw;
+    }
+}
+
+/// Runs one tick of movement for a single controller.
+/// Backends call this from their `fps_controller_move` system.
+#[allow(clippy::too_many_arguments)]
+pub fn move_controller(
+    physics: &impl PhysicsQuery,
+    input: &FpsControllerInput,
+    controller: &FpsController,
+    controller_state: &mut FpsControllerState,
+    shape: ControllerShape,
+    transform: &mut Transform,
+    velocity: &mut Vec3,
+    dt: f32,
+) {
+    controller_state.previous_translation = Some(transform.translation);
+
+    let kinematic = controller.collision_mode == CollisionMode::Kinematic;
+    let mut state = controller_state.movement;
+    state.translation = transform.translation;
+    if !kinematic {
+        state.velocity = *velocity;
+    }
+    let state = step_movement(controller, state, input, shape, physics, dt);
+
+    transform.translation = state.translation;
+    controller_state.movement = state;
+    // Otherwise the physics engine would move the kinematic body a second time
+    *velocity = if kinematic {
+        Vec3::ZERO
+    } else {
+        state.velocity
+    };
+}
+
+/// Computes the next movement state from the current one without touching the ECS.
+/// Only the tunables of `controller` are read, its runtime state is taken from `state` instead.
+/// Useful for servers, bots and tests that need to simulate movement outside of the plugin schedule.
+///
+/// The translation is only advanced with [`CollisionMode::Kinematic`], the other collision modes
+/// leave integrating the velocity to the physics engine, so use that mode for headless simulation.
+pub fn step_movement(
+    controller: &FpsController,
+    mut state: MovementState,
+    input: &FpsControllerInput,
+    shape: ControllerShape,
+    physics: &impl PhysicsQuery,
+    dt: f32,
+) -> MovementState {
+    let shape = shape.with_height(state.height);
+
+    if input.fly {
+        state.move_mode = match state.move_mode {
+            MoveMode::Noclip => MoveMode::Ground,
+            MoveMode::Ground | MoveMode::WallRun | MoveMode::Slide => MoveMode::Noclip,
+        }
+    }
+
+    match state.move_mode {
+        MoveMode::Noclip => {
+            if input.movement == Vec3::ZERO {
+                let friction = controller.fly_friction.clamp(0.0, 1.0);
+                state.velocity *= 1.0 - friction;
+                if state.velocity.length_squared() < f32::EPSILON {
+                    state.velocity = Vec3::ZERO;
+                }
+            } else {
+                let fly_speed = if input.sprint {
+                    controller.fast_fly_speed
+                } else {
+                    controller.fly_speed
+                };
+                let mut move_to_world =
+                    Mat3::from_euler(EulerRot::YXZ, input.yaw, input.pitch, 0.0);
+                move_to_world.z_axis *= -1.0; // Forward is -Z
+                move_to_world.y_axis = Vec3::Y; // Vertical movement aligned with world up
+                state.velocity = move_to_world * input.movement * fly_speed;
+            }
+        }
+        MoveMode::Ground | MoveMode::WallRun | MoveMode::Slide => {
+            let speeds = Vec3::new(controller.side_speed, 0.0, controller.forward_speed);
+            let mut move_to_world = Mat3::from_axis_angle(Vec3::Y, input.yaw);
+            move_to_world.z_axis *= -1.0; // Forward is -Z
+            let mut wish_direction = move_to_world * (input.movement * speeds);
+            let mut wish_speed = wish_direction.length();
+            if wish_speed > f32::EPSILON {
+                // Avoid division by zero
+                wish_direction /= wish_speed; // Effectively normalize, avoid length computation twice
+            }
+            let max_speed = if input.crouch {
+                controller.crouched_speed
+            } else if input.walk {
+                controller.slow_walk_speed
+            } else if input.sprint {
+                controller.run_speed
+            } else {
+                controller.walk_speed
+            };
+            // Partially tilted analog sticks move slower, keys always give full speed
+            let max_speed = max_speed * input.movement.xz().length().min(1.0);
+            wish_speed = f32::min(wish_speed, max_speed);
+            let gravity = if state.velocity.y < 0.0 {
+                controller.gravity * controller.fall_gravity_scale
+            } else {
+                controller.gravity
+            };
+
+            // Without auto hop, holding jump only jumps once until it is released
+            if !input.jump {
+                state.jump_consumed = false;
+            }
+            let fresh_jump_press = input.jump && !state.jump_consumed;
+            let jump_pressed = input.jump && (controller.auto_hop || !state.jump_consumed);
+
+            // A press is remembered for a few ticks so it is not lost just before landing
+            let wants_jump = jump_pressed || state.jump_buffer_tick > 0;
+            state.jump_buffer_tick = if jump_pressed {
+                time_to_ticks(controller.jump_buffer_time, dt)
+            } else {
+                state.jump_buffer_tick.saturating_sub(1)
+            };
+            state.wall_jump_cooldown_tick = state.wall_jump_cooldown_tick.saturating_sub(1);
+            state.slide_boost_cooldown_tick = state.slide_boost_cooldown_tick.saturating_sub(1);
+
+            // Shape cast downwards to find ground
+            // Better than a ray cast as it handles when you are near the edge of a surface
+            if let Some(hit) = physics.cast_shape(
+                // Consider when the controller is right up against a wall
+                // We do not want the shape cast to detect it,
+                // so provide a slightly smaller collider in the XZ plane
+                &shape.scaled_laterally(SLIGHT_SCALE_DOWN),
+                state.translation,
+                -Dir3::Y,
+                controller.grounded_distance,
+            ) {
+                let has_traction =
+                    Vec3::dot(hit.normal, Vec3::Y) > controller.traction_normal_cutoff;
+
+                /* Sliding */
+
+                let was_sliding = state.move_mode == MoveMode::Slide;
+                let slide_speed = state.velocity.xz().length();
+                let sliding = controller.enable_slide
+                    && has_traction
+                    && input.crouch
+                    && if was_sliding {
+                        slide_speed >= controller.slide_end_speed
+                    } else {
+                        slide_speed >= controller.slide_start_speed
+                    };
+                state.move_mode = if sliding {
+                    MoveMode::Slide
+                } else {
+                    MoveMode::Ground
+                };
+                if sliding && !was_sliding && state.slide_boost_cooldown_tick == 0 {
+                    let direction =
+                        Vec3::new(state.velocity.x, 0.0, state.velocity.z).normalize_or_zero();
+                    state.velocity += direction * controller.slide_boost;
+                    state.slide_boost_cooldown_tick =
+                        time_to_ticks(controller.slide_boost_cooldown, dt);
+                }
+
+                // Only apply friction after at least one tick, allows b-hopping without losing speed
+                if state.ground_tick >= 1 && has_traction {
+                    let friction = if sliding {
+                        controller.slide_friction
+                    } else {
+                        controller.friction
+                    };
+                    let lateral_speed = state.velocity.xz().length();
+                    if lateral_speed > controller.friction_speed_cutoff {
+                        let control = f32::max(lateral_speed, controller.stop_speed);
+                        let drop = control * friction * dt;
+                        let new_speed = f32::max((lateral_speed - drop) / lateral_speed, 0.0);
+                        state.velocity.x *= new_speed;
+                        state.velocity.z *= new_speed;
+                    } else {
+                        state.velocity = Vec3::ZERO;
+                    }
+                    if state.ground_tick == 1 {
+                        state.velocity.y = -hit.distance;
+                    }
+                }
+
+                let mut add = if sliding {
+                    // Momentum is kept, only gravity along the slope speeds the slide up
+                    let down = Vec3::NEG_Y * controller.gravity;
+                    (down - Vec3::dot(down, hit.normal) * hit.normal) * dt
+                } else {
+                    acceleration(
+                        wish_direction,
+                        wish_speed,
+                        controller.acceleration,
+                        state.velocity,
+                        dt,
+                    )
+                };
+                if !has_traction {
+                    add.y -= gravity * dt;
+                }
+                state.velocity += add;
+
+                if has_traction {
+                    let linear_velocity = state.velocity;
+                    state.velocity -= Vec3::dot(linear_velocity, hit.normal) * hit.normal;
+
+                    if wants_jump {
+                        start_jump(&mut state, controller.jump_speed);
+                    } else {
+                        state.coyote_tick = time_to_ticks(controller.coyote_time, dt);
+                        state.jumping = false;
+                    }
+                } else {
+                    state.coyote_tick = 0;
+                }
+
+                // Increment ground tick but cap at max value
+                state.ground_tick = state.ground_tick.saturating_add(1);
+                state.air_jumps_used = 0;
+                state.wall_run_tick = 0;
+                state.wall_normal = Vec3::ZERO;
+            } else {
+                state.ground_tick = 0;
+                wish_speed = f32::min(wish_speed, controller.air_speed_cap);
+
+                // Releasing jump while still rising cuts the jump short,
+                // but only if it was held after takeoff, so scrolls and buffered presses jump fully
+                if state.jumping {
+                    if state.velocity.y <= 0.0 {
+                        state.jumping = false;
+                    } else if input.jump {
+                        state.jump_held = true;
+                    } else if state.jump_held {
+                        state.velocity.y *= controller.jump_cut_factor;
+                        state.jumping = false;
+                    }
+                }
+
+                // Still allow jumping for a few ticks after walking off a ledge
+                let mut jumped = false;
+                if wants_jump && state.coyote_tick > 0 {
+                    start_jump(&mut state, controller.jump_speed);
+                    jumped = true;
+                } else {
+                    state.coyote_tick = state.coyote_tick.saturating_sub(1);
+
+                    if fresh_jump_press
+                        && controller.enable_wall_jump
+                        && wall_jump(controller, &mut state, &shape, physics, input.yaw, dt)
+                    {
+                        jumped = true;
+                    } else if fresh_jump_press && state.air_jumps_used < controller.air_jumps {
+                        let lateral = state.velocity.xz();
+                        if wish_speed > f32::EPSILON && lateral != Vec2::ZERO {
+                            // Rotate rather than lerp, so turning around keeps the speed
+                            let angle = lateral.angle_to(wish_direction.xz())
+                                * controller.air_jump_redirect.clamp(0.0, 1.0);
+                            let redirected = Vec2::from_angle(angle).rotate(lateral);
+                            state.velocity.x = redirected.x;
+                            state.velocity.z = redirected.y;
+                        }
+                        start_jump(&mut state, controller.air_jump_speed);
+                        state.air_jumps_used += 1;
+                        jumped = true;
+                    }
+                }
+
+                /* Wall running */
+
+                if controller.enable_wall_run && !jumped {
+                    update_wall_run(controller, &mut state, &shape, physics, wish_direction, dt);
+                } else {
+                    state.move_mode = MoveMode::Ground;
+                }
+
+                if state.move_mode == MoveMode::WallRun {
+                    // Stick to the wall and only move along it
+                    let normal = state.wall_normal;
+                    state.velocity -= Vec3::dot(state.velocity, normal) * normal;
+                    state.velocity.y -= gravity * controller.wall_run_gravity_scale * dt;
+                } else {
+                    let mut add = acceleration(
+                        wish_direction,
+                        wish_speed,
+                        controller.air_acceleration,
+                        state.velocity,
+                        dt,
+                    );
+                    add.y = -gravity * dt;
+                    state.velocity += add;
+
+                    let air_speed = state.velocity.xz().length();
+                    if air_speed > controller.max_air_speed {
+                        let ratio = controller.max_air_speed / air_speed;
+                        state.velocity.x *= ratio;
+                        state.velocity.z *= ratio;
+                    }
+                }
+            }
+
+            /* Crouching */
+
+            let crouch_height = controller.crouch_height;
+            let upright_height = controller.upright_height;
+
+            let crouch_speed = if input.crouch {
+                -controller.crouch_speed
+            } else {
+                controller.uncrouch_speed
+            };
+            state.height += dt * crouch_speed;
+            state.height = state.height.clamp(crouch_height, upright_height);
+
+            let shape = shape.with_height(state.height);
+
+            // Step offset really only works best for cylinders
+            // For capsules the player has to practically teleported to fully step up
+            if matches!(shape, ControllerShape::Cylinder { .. })
+                && controller.collision_mode != CollisionMode::CharacterController
+                && controller.step_offset > f32::EPSILON
+                && state.ground_tick >= 1
+            {
+                // Try putting the player forward, but instead lifted upward by the step offset
+                // If we can find a surface below us, we can adjust our position to be on top of it
+                let future_position = state.translation + state.velocity * dt;
+                let future_position_lifted = future_position + Vec3::Y * controller.step_offset;
+                if let Some(hit) = physics.cast_shape(
+                    &shape,
+                    future_position_lifted,
+                    -Dir3::Y,
+                    controller.step_offset * SLIGHT_SCALE_DOWN,
+                ) {
+                    let has_traction_on_ledge =
+                        Vec3::dot(hit.normal, Vec3::Y) > controller.traction_normal_cutoff;
+                    if has_traction_on_ledge {
+                        state.translation.y += controller.step_offset - hit.distance;
+                    }
+                }
+            }
+
+            // Prevent falling off ledges
+            if controller.enable_ledge_cling
+                && state.ground_tick >= 1
+                && state.move_mode != MoveMode::Slide
+                && input.crouch
+                && !input.jump
+            {
+                for _ in 0..2 {
+                    // Find the component of our velocity that is overhanging and subtract it off
+                    let overhang =
+                        overhang_component(physics, &shape, state.translation, state.velocity, dt);
+                    if let Some(overhang) = overhang {
+                        state.velocity -= overhang;
+                    }
+                }
+                // If we are still overhanging consider unsolvable and freeze
+                if overhang_component(physics, &shape, state.translation, state.velocity, dt)
+                    .is_some()
+                {
+                    state.velocity = Vec3::ZERO;
+                }
+            }
+        }
+    }
+
+    match (controller.collision_mode, state.move_mode) {
+        (CollisionMode::Dynamic, _) => {}
+        (_, MoveMode::Noclip) => state.translation += state.velocity * dt,
+        // The backend character controller moves the player itself
+        (CollisionMode::CharacterController, _) => {}
+        (CollisionMode::Kinematic, _) => {
+            (state.translation, state.velocity) = move_and_slide(
+                physics,
+                &shape.with_height(state.height),
+                state.translation,
+                state.velocity,
+                controller.overbounce,
+                dt,
+            );
+        }
+    }
+
+    state
+}
+
+/// Moves the shape by the velocity over the time step, sliding along any surfaces that are hit.
+/// Returns the new translation and the clipped velocity.
+/// Based off of `TryPlayerMove` from the Source engine.
+pub fn move_and_slide(
+    physics: &impl PhysicsQuery,
+    shape: &ControllerShape,
+    mut translation: Vec3,
+    mut velocity: Vec3,
+    overbounce: f32,
+    dt: f32,
+) -> (Vec3, Vec3) {
+    // Resolve penetration first, otherwise every sweep would report a hit immediately
+    for _ in 0..MAX_DEPENETRATION_ITERATIONS {
+        let Some(hit) = physics.penetration(shape, translation) else {
+            break;
+        };
+        translation += hit.normal * (hit.distance + SKIN_WIDTH);
+        let into_surface = Vec3::dot(velocity, hit.normal);
+        if into_surface < 0.0 {
+            velocity -= into_surface * hit.normal;
+        }
+    }
+
+    let primal_velocity = velocity;
+    let mut planes = [Vec3::ZERO; MAX_CLIP_PLANES];
+    let mut plane_count = 0;
+    let mut time_left = dt;
+
+    for _ in 0..MAX_BUMPS {
+        let Ok((direction, speed)) = Dir3::new_and_length(velocity) else {
+            break;
+        };
+        let distance = speed * time_left;
+        if distance < f32::EPSILON {
+            break;
+        }
+
+        let Some(hit) = physics.cast_shape(shape, translation, direction, distance + SKIN_WIDTH)
+        else {
+            translation += direction * distance;
+            break;
+        };
+        // Stop slightly before the surface so the next sweep does not start in contact
+        let travel = (hit.distance - SKIN_WIDTH).clamp(0.0, distance);
+        translation += direction * travel;
+        time_left -= time_left * travel / distance;
+
+        if plane_count == MAX_CLIP_PLANES {
+            velocity = Vec3::ZERO;
+            break;
+        }
+        planes[plane_count] = hit.normal;
+        plane_count += 1;
+        let planes = &planes[..plane_count];
+
+        // Find a velocity along one of the planes that does not move into any of the others
+        let clipped = planes.iter().enumerate().find_map(|(i, &plane)| {
+            let clipped = clip_velocity(primal_velocity, plane, overbounce);
+            let into_other = planes
+                .iter()
+                .enumerate()
+                .any(|(j, &other)| j != i && Vec3::dot(clipped, other) < 0.0);
+            (!into_other).then_some(clipped)
+        });
+        velocity = match clipped {
+            Some(clipped) => clipped,
+            // Go along the crease
+            None if plane_count == 2 => {
+                let crease = Vec3::cross(planes[0], planes[1]).normalize_or_zero();
+                crease * Vec3::dot(crease, velocity)
+            }
+            // Stuck in a corner
+            None => Vec3::ZERO,
+        };
+
+        // Avoid jittering back and forth in corners
+        if Vec3::dot(velocity, primal_velocity) <= 0.0 {
+            velocity = Vec3::ZERO;
+            break;
+        }
+    }
+
+    (translation, velocity)
+}
+
+/// Removes the component of the velocity that goes into the plane.
+pub fn clip_velocity(velocity: Vec3, normal: Vec3, overbounce: f32) -> Vec3 {
+    let backoff = Vec3::dot(velocity, normal);
+    let backoff = if backoff < 0.0 {
+        backoff * overbounce
+    } else {
+        backoff / overbounce
+    };
+    let mut clipped = velocity - normal * backoff;
+    // Make sure there is no residual movement into the plane
+    let adjust = Vec3::dot(clipped, normal);
+    if adjust < 0.0 {
+        clipped -= normal * adjust;
+    }
+    clipped
+}
+
+fn overhang_component(
+    physics: &impl PhysicsQuery,
+    shape: &ControllerShape,
+    translation: Vec3,
+    velocity: Vec3,
+    dt: f32,
+) -> Option<Vec3> {
+    let Ok(direction) = Dir3::new(-velocity) else {
+        return None;
+    };
+
+    // Cast a segment (zero radius capsule) from our next position back towards us (sweeping a rectangle)
+    // If there is a ledge in front of us we will hit the edge of it
+    // We can use the normal of the hit to subtract off the component that is overhanging
+    let cast_capsule = ControllerShape::Capsule {
+        radius: 0.01,
+        half_height: 0.25,
+    };
+    let future_position = translation - shape.y_offset() + velocity * dt;
+
+    if let Some(hit) = physics.cast_shape(
+        &cast_capsule,
+        future_position,
+        direction,
+        velocity.length() * 0.5,
+    ) {
+        let cast = physics.cast_ray(future_position + Vec3::Y * 0.125, -Dir3::Y, 0.375);
+        // Make sure that this is actually a ledge, e.g. there is no ground in front of us
+        if cast.is_none() {
+            let normal = -hit.normal;
+            let alignment = Vec3::dot(velocity, normal);
+            return Some(alignment * normal);
+        }
+    }
+    None
+}
+
+fn acceleration(
+    wish_direction: Vec3,
+    wish_speed: f32,
+    acceleration: f32,
+    velocity: Vec3,
+    dt: f32,
+) -> Vec3 {
+    let velocity_projection = Vec3::dot(velocity, wish_direction);
+    let add_speed = wish_speed - velocity_projection;
+    if add_speed <= 0.0 {
+        return Vec3::ZERO;
+    }
+
+    let acceleration_speed = f32::min(acceleration * wish_speed * dt, add_speed);
+    wish_direction * acceleration_speed
+}
+
+/// Starts, keeps or ends a wall run while airborne.
+fn update_wall_run(
+    controller: &FpsController,
+    state: &mut MovementState,
+    shape: &ControllerShape,
+    physics: &impl PhysicsQuery,
+    wish_direction: Vec3,
+    dt: f32,
+) {
+    let lateral_velocity = Vec3::new(state.velocity.x, 0.0, state.velocity.z);
+    let speed = lateral_velocity.length();
+    let wall_running = state.move_mode == MoveMode::WallRun;
+
+    let wall = if wall_running {
+        find_wall(
+            physics,
+            controller,
+            shape,
+            state.translation,
+            &[-state.wall_normal],
+        )
+    } else if speed > f32::EPSILON {
+        // Look for walls to either side and in front of the direction of travel
+        let forward = lateral_velocity / speed;
+        let side = forward.cross(Vec3::Y);
+        find_wall(
+            physics,
+            controller,
+            shape,
+            state.translation,
+            &[side, -side, forward],
+        )
+    } else {
+        None
+    };
+    let wall = wall.filter(|hit| {
+        // Running on the wall just jumped off would undo the jump
+        let jumped_off = state.wall_jump_cooldown_tick > 0
+            && Vec3::dot(hit.normal, state.wall_jump_normal) >= SAME_WALL_ALIGNMENT;
+        if !wall_running && jumped_off {
+            return false;
+        }
+        let toward_wall = Vec3::dot(wish_direction, -hit.normal);
+        // Keep running while not steering away, but only start when holding toward the wall
+        let holding = if wall_running {
+            wish_direction != Vec3::ZERO && toward_wall >= 0.0
+        } else {
+            toward_wall > 0.0
+        };
+        // Only the speed along the wall counts, running head on into it is not a wall run
+        let along_wall = lateral_velocity - Vec3::dot(lateral_velocity, hit.normal) * hit.normal;
+        holding && along_wall.length() >= controller.wall_run_min_speed
+    });
+
+    let Some(hit) = wall else {
+        state.move_mode = MoveMode::Ground;
+        return;
+    };
+    // A new wall gets a fresh timer, the same wall only continues the old one
+    if !wall_running && Vec3::dot(hit.normal, state.wall_normal) < SAME_WALL_ALIGNMENT {
+        state.wall_run_tick = 0;
+    }
+    state.wall_run_tick = state.wall_run_tick.saturating_add(1);
+    if state.wall_run_tick > time_to_ticks(controller.wall_run_time, dt) {
+        state.move_mode = MoveMode::Ground;
+        return;
+    }
+    if !wall_running {
+        // Do not keep falling while running
+        state.velocity.y = state.velocity.y.max(0.0);
+    }
+    state.wall_normal = hit.normal;
+    state.move_mode = MoveMode::WallRun;
+}
+
+/// Casts the shape sideways in each direction and returns the closest near vertical wall,
+/// with the normal flattened to the horizontal plane.
+fn find_wall(
+    physics: &impl PhysicsQuery,
+    controller: &FpsController,
+    shape: &ControllerShape,
+    translation: Vec3,
+    directions: &[Vec3],
+) -> Option<ShapeHit> {
+    directions
+        .iter()
+        .filter_map(|&direction| Dir3::new(direction).ok())
+        .filter_map(|direction| {
+            physics.cast_shape(shape, translation, direction, controller.wall_distance)
+        })
+        .filter(|hit| hit.normal.y.abs() < MAX_WALL_NORMAL_Y)
+        .filter_map(|hit| {
+            let normal = Vec3::new(hit.normal.x, 0.0, hit.normal.z).try_normalize()?;
+            Some(ShapeHit { normal, ..hit })
+        })
+        .min_by(|a, b| a.distance.total_cmp(&b.distance))
+}
+
+/// Jumps off a wall next to the player, returns false if there is none or it is on cooldown.
+fn wall_jump(
+    controller: &FpsController,
+    state: &mut MovementState,
+    shape: &ControllerShape,
+    physics: &impl PhysicsQuery,
+    yaw: f32,
+    dt: f32,
+) -> bool {
+    let view = Quat::from_rotation_y(yaw);
+    let around_view = [
+        view * Vec3::NEG_Z,
+        view * Vec3::Z,
+        view * Vec3::X,
+        view * Vec3::NEG_X,
+    ];
+    let running_wall = [-state.wall_normal];
+    let directions: &[Vec3] = if state.move_mode == MoveMode::WallRun {
+        &running_wall
+    } else {
+        &around_view
+    };
+    let Some(hit) = find_wall(physics, controller, shape, state.translation, directions) else {
+        return false;
+    };
+    if state.wall_jump_cooldown_tick > 0
+        && Vec3::dot(hit.normal, state.wall_jump_normal) >= SAME_WALL_ALIGNMENT
+    {
+        return false;
+    }
+
+    // Replace any velocity into or away from the wall with the push off it
+    let normal = hit.normal;
+    state.velocity -= Vec3::dot(state.velocity, normal) * normal;
+    state.velocity += normal * controller.wall_jump_outward_speed;
+    start_jump(state, controller.wall_jump_upward_speed);
+    state.wall_jump_normal = normal;
+    state.wall_jump_cooldown_tick = time_to_ticks(controller.wall_jump_cooldown, dt);
+    true
+}
+
+fn start_jump(state: &mut MovementState, jump_speed: f32) {
+    state.move_mode = MoveMode::Ground;
+    state.velocity.y = jump_speed;
+    state.jump_buffer_tick = 0;
+    state.coyote_tick = 0;
+    state.jump_consumed = true;
+    state.jumping = true;
+    state.jump_held = false;
+}
+
+/// Number of fixed ticks covering the duration, saturating at the range of the tick counters.
+fn time_to_ticks(seconds: f32, dt: f32) -> u32 {
+    if dt > 0.0 {
+        (seconds / dt).round() as u32
+    } else {
+        0
+    }
+}
+
+/// Sends messages for changes of the move mode made by the last tick.
+pub fn fps_controller_move_mode_messages(
+    mut query: Query<(Entity, &mut FpsControllerState)>,
+    mut wall_run_started: MessageWriter<WallRunStarted>,
+    mut wall_run_ended: MessageWriter<WallRunEnded>,
+    mut slide_started: MessageWriter<SlideStarted>,
+    mut slide_ended: MessageWriter<SlideEnded>,
+) {
+    for (entity, mut state) in query.iter_mut() {
+        let (previous, current) = (state.previous_move_mode, state.movement.move_mode);
+        if previous == current {
+            continue;
+        }
+        match previous {
+            MoveMode::WallRun => {
+                wall_run_ended.write(WallRunEnded { entity });
+            }
+            MoveMode::Slide => {
+                slide_ended.write(SlideEnded { entity });
+            }
+            MoveMode::Noclip | MoveMode::Ground => {}
+        }
+        match current {
+            MoveMode::WallRun => {
+                wall_run_started.write(WallRunStarted {
+                    entity,
+                    wall_normal: state.movement.wall_normal,
+                });
+            }
+            MoveMode::Slide => {
+                slide_started.write(SlideStarted { entity });
+            }
+            MoveMode::Noclip | MoveMode::Ground => {}
+        }
+        state.previous_move_mode = current;
+    }
+}
+
+pub fn fps_controller_update_collider<C: ControllerCollider>(
+    mut query: Query<(&FpsControllerState, &mut C), With<LogicalPlayer>>,
+) {
+    for (state, mut collider) in query.iter_mut() {
+        let shape = collider
+            .controller_shape()
+            .with_height(state.movement.height);
+        collider.set_controller_shape(shape);
+    }
+}
+
+//     ____                 __
+//    / __ \___  ____  ____/ /__  _____
+//   / /_/ / _ \/ __ \/ __  / _ \/ ___/
+//  / _, _/  __/ / / / /_/ /  __/ /
+// /_/ |_|\___/_/ /_/\__,_/\___/_/
+
+/// Places the camera at the logical player, interpolated between the last two fixed ticks
+/// when `fixed_movement_schedule` is set, added with [`IntoSystem::with_input`].
+#[allow(clippy::type_complexity)]
+pub fn fps_controller_render<C: ControllerCollider>(
+    InMut(fixed_movement_schedule): InMut<bool>,
+    fixed_time: Res<Time<Fixed>>,
+    mut render_query: Query<(&mut Transform, &RenderPlayer), With<RenderPlayer>>,
+    logical_query: Query<
+        (&Transform, &C, &FpsControllerState, &CameraConfig),
+        (With<LogicalPlayer>, Without<RenderPlayer>),
+    >,
+) {
+    // Outside the fixed schedules the logical player already moved this frame
+    let t = if *fixed_movement_schedule {
+        fixed_time.overstep_fraction()
+    } else {
+        1.0
+    };
+
+    for (mut render_transform, render_player) in render_query.iter_mut() {
+        if let Ok((logical_transform, collider, state, camera_config)) =
+            logical_query.get(render_player.logical_entity)
+        {
+            let previous = state.previous_translation;
+            let current = logical_transform.translation;
+            let interpolated = previous.unwrap_or(current).lerp(current, t);
+            let collider_offset = collider.controller_shape().y_offset();
+            let camera_offset = Vec3::Y * camera_config.height_offset;
+            render_transform.translation = interpolated + collider_offset + camera_offset;
+            render_transform.rotation =
+                Quat::from_euler(EulerRot::YXZ, state.yaw, state.pitch, 0.0);
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) const DT: f32 = 1.0 / 64.0;
+
+    pub(crate) const SHAPE: ControllerShape = ControllerShape::Cylinder {
+        radius: 0.5,
+        half_height: 1.5,
+    };
+
+    /// Physics made of infinite planes given by their normal and distance from the origin.
+    pub(crate) fn planes(planes: &[(Vec3, f32)]) -> impl Fn(&SpatialCast) -> Option<ShapeHit> + '_ {
+        move |cast| {
+            // Distance from the center of the shape to its surface along a plane normal
+            let extent = |shape: &ControllerShape, normal: Vec3| match *shape {
+                ControllerShape::Cylinder {
+                    radius,
+                    half_height,
+                } => normal.y.abs() * half_height + normal.xz().length() * radius,
+                ControllerShape::Capsule {
+                    radius,
+                    half_height,
+                } => normal.y.abs() * half_height + radius,
+            };
+            let hits = planes.iter().filter_map(|&(normal, offset)| {
+                let (origin, extent, direction, max_distance) = match *cast {
+                    SpatialCast::Shape {
+                        shape,
+                        origin,
+                        direction,
+                        max_distance,
+                    } => (origin, extent(&shape, normal), direction, max_distance),
+                    SpatialCast::Ray {
+                        origin,
+                        direction,
+                        max_distance,
+                    } => (origin, 0.0, direction, max_distance),
+                    SpatialCast::Penetration { shape, origin } => {
+                        let gap = Vec3::dot(origin, normal) - offset - extent(&shape, normal);
+                        return (gap < 0.0).then_some(ShapeHit {
+                            distance: -gap,
+                            normal,
+                        });
+                    }
+                };
+                let gap = Vec3::dot(origin, normal) - offset - extent;
+                let approach = -Vec3::dot(*direction, normal);
+                if gap < 0.0 || approach <= 0.0 {
+                    return None;
+                }
+                let distance = gap / approach;
+                (distance <= max_distance).then_some(ShapeHit { distance, normal })
+            });
+            if matches!(cast, SpatialCast::Penetration { .. }) {
+                hits.max_by(|a, b| a.distance.total_cmp(&b.distance))
+            } else {
+                hits.min_by(|a, b| a.distance.total_cmp(&b.distance))
+            }
+        }
+    }
+
+    fn kinematic_controller() -> FpsController {
+        FpsController {
+            collision_mode: CollisionMode::Kinematic,
+            ..default()
+        }
+    }
+
+    fn simulate(
+        controller: &FpsController,
+        mut state: MovementState,
+        input: &FpsControllerInput,
+        physics: &impl PhysicsQuery,
+        ticks: usize,
+    ) -> MovementState {
+        for _ in 0..ticks {
+            state = step_movement(controller, state, input, SHAPE, physics, DT);
+        }
+        state
+    }
+
+    #[test]
+    fn stands_on_flat_floor() {
+        let controller = kinematic_controller();
+        let mut state = MovementState::new(&controller);
+        state.translation = Vec3::Y * 1.5;
+        let floor = [(Vec3::Y, 0.0)];
+
+        let state = simulate(
+            &controller,
+            state,
+            &FpsControllerInput::default(),
+            &planes(&floor),
+            64,
+        );
+
+        assert!(state.ground_tick > 0);
+        assert!((state.translation.y - 1.5).abs() < 0.1);
+        assert!(state.velocity.length() < 0.1);
+    }
+
+    #[test]
+    fn capsule_stands_on_flat_floor() {
+        let controller = kinematic_controller();
+        let mut state = MovementState::new(&controller);
+        state.translation = Vec3::Y * 1.5;
+        let floor = [(Vec3::Y, 0.0)];
+        let physics = planes(&floor);
+        let capsule = ControllerShape::Capsule {
+            radius: 0.5,
+            half_height: 1.0,
+        };
+
+        for _ in 0..64 {
+            state = step_movement(
+                &controller,
+                state,
+                &FpsControllerInput::default(),
+                capsule,
+                &physics,
+                DT,
+            );
+        }
+
+        assert!(state.ground_tick > 0);
+        assert!((state.translation.y - 1.5).abs() < 0.1);
+    }
+
+    #[test]
+    fn walks_forward_on_flat_floor() {
+        let controller = kinematic_controller();
+        let mut state = MovementState::new(&controller);
+        state.translation = Vec3::Y * 1.5;
+        let floor = [(Vec3::Y, 0.0)];
+        let input = FpsControllerInput {
+            movement: Vec3::Z,
+            ..default()
+        };
+
+        let state = simulate(&controller, state, &input, &planes(&floor), 64);
+
+        assert!(state.translation.z < -5.0);
+        assert!(state.translation.x.abs() < 0.01);
+        assert!((state.translation.y - 1.5).abs() < 0.1);
+        assert!((state.velocity.length() - controller.walk_speed).abs() < 0.5);
+    }
+
+    #[test]
+    fn falls_without_floor() {
+        let controller = kinematic_controller();
+        let state = simulate(
+            &controller,
+            MovementState::new(&controller),
+            &FpsControllerInput::default(),
+            &planes(&[]),
+            32,
+        );
+
+        assert_eq!(state.ground_tick, 0);
+        assert!(state.velocity.y < 0.0);
+        assert!(state.translation.y < 0.0);
+    }
+
+    /// Highest point reached when feeding the inputs one per tick, then nothing until landing.
+    fn jump_apex(controller: &FpsController, inputs: &[FpsControllerInput]) -> f32 {
+        let floor = [(Vec3::Y, 0.0)];
+        let physics = planes(&floor);
+        let mut state = MovementState::new(controller);
+        state.translation = Vec3::Y * 1.5;
+        state = simulate(
+            controller,
+            state,
+            &FpsControllerInput::default(),
+            &physics,
+            8,
+        );
+        let idle = FpsControllerInput::default();
+        let mut apex = state.translation.y;
+        for input in inputs.iter().chain(std::iter::repeat_n(&idle, 128)) {
+            state = step_movement(controller, state, input, SHAPE, &physics, DT);
+            apex = apex.max(state.translation.y);
+        }
+        apex
+    }
+
+    fn jump_input() -> FpsControllerInput {
+        FpsControllerInput {
+            jump: true,
+            ..default()
+        }
+    }
+
+    #[test]
+    fn releasing_held_jump_cuts_it() {
+        let controller = FpsController {
+            jump_cut_factor: 0.5,
+            ..kinematic_controller()
+        };
+        let full = jump_apex(&controller, &vec![jump_input(); 64]);
+        let cut = jump_apex(&controller, &vec![jump_input(); 4]);
+        assert!(cut < full - 0.5, "cut {cut} full {full}");
+    }
+
+    #[test]
+    fn single_tick_jump_is_not_cut() {
+        let controller = FpsController {
+            jump_cut_factor: 0.5,
+            ..kinematic_controller()
+        };
+        let full = jump_apex(&controller, &vec![jump_input(); 64]);
+        // Like a scroll wheel impulse
+        let tapped = jump_apex(&controller, &[jump_input()]);
+        assert!((tapped - full).abs() < 0.01, "tapped {tapped} full {full}");
+    }
+
+    #[test]
+    fn slides_along_wall() {
+        // Wall at x = 1 facing -X
+        let wall = [(Vec3::NEG_X, -1.0)];
+        let (translation, velocity) = move_and_slide(
+            &planes(&wall),
+            &SHAPE,
+            Vec3::ZERO,
+            Vec3::new(10.0, 0.0, -10.0),
+            1.0,
+            0.5,
+        );
+
+        assert!(translation.x <= 0.5);
+        assert!(translation.x > 0.4);
+        assert!(translation.z < -4.0);
+        assert!(velocity.x.abs() < 1e-4);
+        assert!((velocity.z + 10.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn pushed_out_of_wall() {
+        let wall = [(Vec3::NEG_X, -1.0)];
+        let (translation, velocity) =
+            move_and_slide(&planes(&wall), &SHAPE, Vec3::X * 0.75, Vec3::ZERO, 1.0, DT);
+
+        assert!(translation.x <= 0.5);
+        assert_eq!(velocity, Vec3::ZERO);
+    }
+
+    #[test]
+    fn clips_velocity_into_plane() {
+        let clipped = clip_velocity(Vec3::new(3.0, -2.0, 1.0), Vec3::Y, 1.0);
+        assert_eq!(clipped, Vec3::new(3.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn overbounce_pushes_away_from_plane() {
+        let clipped = clip_velocity(Vec3::new(3.0, -2.0, 1.0), Vec3::Y, 1.5);
+        assert_eq!(clipped, Vec3::new(3.0, 1.0, 1.0));
+    }
+
+    /// Runs the inputs one per tick and returns the state after each of them.
+    fn run_inputs(
+        controller: &FpsController,
+        mut state: MovementState,
+        physics: &impl PhysicsQuery,
+        inputs: &[FpsControllerInput],
+    ) -> Vec<MovementState> {
+        inputs
+            .iter()
+            .map(|input| {
+                state = step_movement(controller, state, input, SHAPE, physics, DT);
+                state
+            })
+            .collect()
+    }
+
+    fn started_jump(controller: &FpsController, states: &[MovementState]) -> bool {
+        states
+            .iter()
+            .any(|state| state.velocity.y > controller.jump_speed - 0.5)
+    }
+
+    /// Falls one unit onto the floor, pressing jump for a single tick partway down.
+    fn buffered_jump(controller: &FpsController) -> bool {
+        let floor = [(Vec3::Y, 0.0)];
+        let mut state = MovementState::new(controller);
+        state.translation = Vec3::Y * 2.5;
+        // Landing takes about 19 ticks, so this is 9 ticks early
+        let mut inputs = vec![FpsControllerInput::default(); 40];
+        inputs[10] = jump_input();
+        let states = run_inputs(controller, state, &planes(&floor), &inputs);
+        assert!(states[..10].iter().all(|state| state.ground_tick == 0));
+        started_jump(controller, &states)
+    }
+
+    #[test]
+    fn buffered_jump_press_jumps_on_landing() {
+        let controller = FpsController {
+            jump_buffer_time: 0.2,
+            ..kinematic_controller()
+        };
+        assert!(buffered_jump(&controller));
+    }
+
+    #[test]
+    fn early_jump_press_is_lost_without_buffer() {
+        assert!(!buffered_jump(&kinematic_controller()));
+    }
+
+    /// Walks off the floor and presses jump for a single tick a few ticks later.
+    fn coyote_jump(controller: &FpsController) -> bool {
+        let floor = [(Vec3::Y, 0.0)];
+        let mut state = MovementState::new(controller);
+        state.translation = Vec3::Y * 1.5;
+        let state = simulate(
+            controller,
+            state,
+            &FpsControllerInput::default(),
+            &planes(&floor),
+            8,
+        );
+        assert!(state.ground_tick > 0);
+
+        // The floor ends
+        let mut inputs = vec![FpsControllerInput::default(); 8];
+        inputs[3] = jump_input();
+        let states = run_inputs(controller, state, &planes(&[]), &inputs);
+        assert_eq!(states[2].ground_tick, 0);
+        started_jump(controller, &states)
+    }
+
+    #[test]
+    fn coyote_time_allows_jumping_after_leaving_ground() {
+        let controller = FpsController {
+            coyote_time: 0.1,
+            ..kinematic_controller()
+        };
+        assert!(coyote_jump(&controller));
+    }
+
+    #[test]
+    fn no_jump_after_leaving_ground_without_coyote_time() {
+        assert!(!coyote_jump(&kinematic_controller()));
+    }
+
+    #[test]
+    fn coyote_time_runs_out() {
+        let controller = FpsController {
+            coyote_time: 0.03,
+            ..kinematic_controller()
+        };
+        assert!(!coyote_jump(&controller));
+    }
+
+    /// Number of jumps started on a flat floor when feeding the inputs one per tick.
+    fn count_jumps(controller: &FpsController, inputs: &[FpsControllerInput]) -> usize {
+        let floor = [(Vec3::Y, 0.0)];
+        let mut state = MovementState::new(controller);
+        state.translation = Vec3::Y * 1.5;
+        let mut states = vec![state];
+        states.extend(run_inputs(controller, state, &planes(&floor), inputs));
+        let takeoff = controller.jump_speed - 0.5;
+        states
+            .windows(2)
+            .filter(|pair| pair[0].velocity.y < takeoff && pair[1].velocity.y >= takeoff)
+            .count()
+    }
+
+    #[test]
+    fn holding_jump_hops_repeatedly_with_auto_hop() {
+        let controller = kinematic_controller();
+        // Each hop takes about 48 ticks
+        assert!(count_jumps(&controller, &vec![jump_input(); 160]) >= 3);
+    }
+
+    #[test]
+    fn holding_jump_hops_once_without_auto_hop() {
+        let controller = FpsController {
+            auto_hop: false,
+            ..kinematic_controller()
+        };
+        assert_eq!(count_jumps(&controller, &vec![jump_input(); 160]), 1);
+    }
+
+    #[test]
+    fn pressing_jump_again_hops_without_auto_hop() {
+        let controller = FpsController {
+            auto_hop: false,
+            ..kinematic_controller()
+        };
+        let mut inputs = vec![jump_input(); 160];
+        inputs[100] = FpsControllerInput::default();
+        assert_eq!(count_jumps(&controller, &inputs), 2);
+    }
+
+    fn air_jumper() -> FpsController {
+        FpsController {
+            air_jumps: 2,
+            ..kinematic_controller()
+        }
+    }
+
+    /// Starts high in the air with the given horizontal velocity.
+    fn airborne(controller: &FpsController, velocity: Vec3) -> MovementState {
+        let mut state = MovementState::new(controller);
+        state.translation = Vec3::Y * 100.0;
+        state.velocity = velocity;
+        state
+    }
+
+    /// Number of ticks in which the upward velocity was set by a jump.
+    fn count_air_jumps(controller: &FpsController, states: &[MovementState]) -> usize {
+        let takeoff = controller.air_jump_speed - 0.5;
+        states
+            .windows(2)
+            .filter(|pair| pair[0].velocity.y < takeoff && pair[1].velocity.y >= takeoff)
+            .count()
+    }
+
+    /// Alternates a press and a release every tick.
+    fn tapping_jump(ticks: usize) -> Vec<FpsControllerInput> {
+        (0..ticks)
+            .map(|tick| FpsControllerInput {
+                jump: tick % 2 == 0,
+                ..default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn air_jumps_are_limited() {
+        let controller = air_jumper();
+        let start = airborne(&controller, Vec3::ZERO);
+        let mut states = vec![start];
+        states.extend(run_inputs(
+            &controller,
+            start,
+            &planes(&[]),
+            &tapping_jump(40),
+        ));
+        assert_eq!(count_air_jumps(&controller, &states), 2);
+        assert_eq!(states.last().unwrap().air_jumps_used, 2);
+    }
+
+    #[test]
+    fn air_jump_needs_fresh_press() {
+        let controller = air_jumper();
+        let start = airborne(&controller, Vec3::ZERO);
+        let mut states = vec![start];
+        states.extend(run_inputs(
+            &controller,
+            start,
+            &planes(&[]),
+            &vec![jump_input(); 40],
+        ));
+        assert_eq!(count_air_jumps(&controller, &states), 1);
+    }
+
+    #[test]
+    fn air_jumps_reset_on_landing() {
+        let controller = air_jumper();
+        let floor = [(Vec3::Y, 0.0)];
+        let mut state = MovementState::new(&controller);
+        state.translation = Vec3::Y * 1.5;
+        state.air_jumps_used = controller.air_jumps;
+        let state = simulate(
+            &controller,
+            state,
+            &FpsControllerInput::default(),
+            &planes(&floor),
+            4,
+        );
+        assert_eq!(state.air_jumps_used, 0);
+    }
+
+    #[test]
+    fn air_jump_turns_toward_wish_direction_keeping_speed() {
+        let controller = FpsController {
+            air_jump_redirect: 0.5,
+            ..air_jumper()
+        };
+        let start = airborne(&controller, Vec3::X * 10.0);
+        // Wish to go the opposite way, half the redirect turns by a right angle
+        let input = FpsControllerInput {
+            jump: true,
+            movement: Vec3::NEG_X,
+            ..default()
+        };
+        let state = step_movement(&controller, start, &input, SHAPE, &planes(&[]), DT);
+        let lateral = state.velocity.xz();
+        assert!(lateral.length() > 9.5, "lost speed {lateral}");
+        assert!(lateral.x.abs() < 1.0, "not turned {lateral}");
+    }
+
+    #[test]
+    fn full_air_jump_redirect_turns_around() {
+        let controller = air_jumper();
+        let start = airborne(&controller, Vec3::X * 10.0);
+        let input = FpsControllerInput {
+            jump: true,
+            movement: Vec3::NEG_X,
+            ..default()
+        };
+        let state = step_movement(&controller, start, &input, SHAPE, &planes(&[]), DT);
+        assert!(state.velocity.x < -9.5, "not turned {}", state.velocity);
+    }
+
+    // Wall at x = 1 facing -X, within wall distance of a player at the origin
+    const WALL: [(Vec3, f32); 1] = [(Vec3::NEG_X, -1.0)];
+    const NEXT_TO_WALL: Vec3 = Vec3::new(0.3, 100.0, 0.0);
+
+    fn wall_runner() -> FpsController {
+        FpsController {
+            enable_wall_run: true,
+            ..kinematic_controller()
+        }
+    }
+
+    /// Forward along the wall and toward it.
+    fn toward_wall_input() -> FpsControllerInput {
+        FpsControllerInput {
+            movement: Vec3::new(1.0, 0.0, 1.0),
+            ..default()
+        }
+    }
+
+    fn next_to_wall(controller: &FpsController, velocity: Vec3) -> MovementState {
+        let mut state = MovementState::new(controller);
+        state.translation = NEXT_TO_WALL;
+        state.velocity = velocity;
+        state
+    }
+
+    #[test]
+    fn wall_run_starts_when_fast_and_holding_toward_wall() {
+        let controller = wall_runner();
+        let state = step_movement(
+            &controller,
+            next_to_wall(&controller, Vec3::NEG_Z * 10.0),
+            &toward_wall_input(),
+            SHAPE,
+            &planes(&WALL),
+            DT,
+        );
+        assert_eq!(state.move_mode, MoveMode::WallRun);
+        assert_eq!(state.wall_normal, Vec3::NEG_X);
+    }
+
+    #[test]
+    fn wall_run_needs_speed_along_wall() {
+        let controller = wall_runner();
+        for velocity in [Vec3::NEG_Z * 5.0, Vec3::X * 10.0] {
+            let state = step_movement(
+                &controller,
+                next_to_wall(&controller, velocity),
+                &toward_wall_input(),
+                SHAPE,
+                &planes(&WALL),
+                DT,
+            );
+            assert_eq!(state.move_mode, MoveMode::Ground, "ran at {velocity}");
+        }
+    }
+
+    #[test]
+    fn wall_run_needs_holding_toward_wall() {
+        let controller = wall_runner();
+        let input = FpsControllerInput {
+            movement: Vec3::Z,
+            ..default()
+        };
+        let state = step_movement(
+            &controller,
+            next_to_wall(&controller, Vec3::NEG_Z * 10.0),
+            &input,
+            SHAPE,
+            &planes(&WALL),
+            DT,
+        );
+        assert_eq!(state.move_mode, MoveMode::Ground);
+    }
+
+    #[test]
+    fn wall_run_ends_after_wall_run_time() {
+        let controller = FpsController {
+            wall_run_time: 0.5,
+            ..wall_runner()
+        };
+        let states = run_inputs(
+            &controller,
+            next_to_wall(&controller, Vec3::NEG_Z * 10.0),
+            &planes(&WALL),
+            &vec![toward_wall_input(); 64],
+        );
+        let ran = states
+            .iter()
+            .take_while(|state| state.move_mode == MoveMode::WallRun)
+            .count();
+        assert_eq!(ran, 32);
+        // Not restarted on the same wall without touching the ground
+        assert!(states[ran..]
+            .iter()
+            .all(|state| state.move_mode == MoveMode::Ground));
+    }
+
+    #[test]
+    fn wall_run_reduces_gravity() {
+        let controller = wall_runner();
+        let ticks = 16;
+        let state = simulate(
+            &controller,
+            next_to_wall(&controller, Vec3::NEG_Z * 10.0),
+            &toward_wall_input(),
+            &planes(&WALL),
+            ticks,
+        );
+        assert_eq!(state.move_mode, MoveMode::WallRun);
+        let expected = -controller.gravity * controller.wall_run_gravity_scale * DT * ticks as f32;
+        assert!(
+            (state.velocity.y - expected).abs() < 0.01,
+            "fell at {} instead of {expected}",
+            state.velocity.y
+        );
+    }
+
+    fn wall_jumper() -> FpsController {
+        FpsController {
+            enable_wall_jump: true,
+            ..kinematic_controller()
+        }
+    }
+
+    #[test]
+    fn wall_jump_launches_outward_and_upward() {
+        let controller = wall_jumper();
+        let state = step_movement(
+            &controller,
+            next_to_wall(&controller, Vec3::ZERO),
+            &jump_input(),
+            SHAPE,
+            &planes(&WALL),
+            DT,
+        );
+        assert!(
+            (state.velocity.x + controller.wall_jump_outward_speed).abs() < 0.01,
+            "pushed off at {}",
+            state.velocity
+        );
+        assert!(state.velocity.y > controller.wall_jump_upward_speed - 0.5);
+        assert_eq!(state.wall_jump_normal, Vec3::NEG_X);
+    }
+
+    #[test]
+    fn wall_jump_needs_a_wall() {
+        let controller = wall_jumper();
+        let state = step_movement(
+            &controller,
+            next_to_wall(&controller, Vec3::ZERO),
+            &jump_input(),
+            SHAPE,
+            &planes(&[]),
+            DT,
+        );
+        assert!(state.velocity.y < 0.0);
+    }
+
+    /// Jumps off the wall at x = 1, then presses jump again next to the given position.
+    fn wall_jump_again(
+        controller: &FpsController,
+        walls: &[(Vec3, f32)],
+        position: Vec3,
+    ) -> MovementState {
+        let physics = planes(walls);
+        let mut state = next_to_wall(controller, Vec3::ZERO);
+        state = step_movement(controller, state, &jump_input(), SHAPE, &physics, DT);
+        state = simulate(
+            controller,
+            state,
+            &FpsControllerInput::default(),
+            &physics,
+            8,
+        );
+        state.translation = position;
+        state.velocity = Vec3::ZERO;
+        step_movement(controller, state, &jump_input(), SHAPE, &physics, DT)
+    }
+
+    #[test]
+    fn same_wall_is_refused_during_cooldown() {
+        let controller = wall_jumper();
+        let state = wall_jump_again(&controller, &WALL, NEXT_TO_WALL);
+        assert!(state.velocity.y < 0.0);
+    }
+
+    #[test]
+    fn same_wall_is_allowed_after_cooldown() {
+        let controller = FpsController {
+            wall_jump_cooldown: 0.1,
+            ..wall_jumper()
+        };
+        let state = wall_jump_again(&controller, &WALL, NEXT_TO_WALL);
+        assert!(state.velocity.y > controller.wall_jump_upward_speed - 0.5);
+    }
+
+    #[test]
+    fn other_wall_is_allowed_during_cooldown() {
+        let controller = wall_jumper();
+        // Second wall at x = -1 facing +X
+        let walls = [WALL[0], (Vec3::X, -1.0)];
+        let state = wall_jump_again(
+            &controller,
+            &walls,
+            NEXT_TO_WALL * Vec3::new(-1.0, 1.0, 1.0),
+        );
+        assert!(state.velocity.y > controller.wall_jump_upward_speed - 0.5);
+        assert!(state.velocity.x > controller.wall_jump_outward_speed - 0.5);
+    }
+
+    fn slider() -> FpsController {
+        FpsController {
+            enable_slide: true,
+            ..kinematic_controller()
+        }
+    }
+
+    fn crouch_forward() -> FpsControllerInput {
+        FpsControllerInput {
+            movement: Vec3::Z,
+            crouch: true,
+            ..default()
+        }
+    }
+
+    /// Runs forward on the flat floor at the given speed, already crouched down.
+    fn running(controller: &FpsController, speed: f32) -> MovementState {
+        let mut state = MovementState::new(controller);
+        state.height = controller.crouch_height;
+        state.translation = Vec3::Y * state.height * 0.5;
+        state.velocity = Vec3::NEG_Z * speed;
+        state.ground_tick = 8;
+        state
+    }
+
+    fn lateral_speed(state: &MovementState) -> f32 {
+        state.velocity.xz().length()
+    }
+
+    #[test]
+    fn slide_starts_fast_with_boost() {
+        let controller = slider();
+        let floor = [(Vec3::Y, 0.0)];
+        let state = step_movement(
+            &controller,
+            running(&controller, 12.0),
+            &crouch_forward(),
+            SHAPE,
+            &planes(&floor),
+            DT,
+        );
+        assert_eq!(state.move_mode, MoveMode::Slide);
+        assert!(lateral_speed(&state) > 12.0 + controller.slide_boost - 0.5);
+    }
+
+    #[test]
+    fn slide_needs_start_speed() {
+        let controller = slider();
+        let floor = [(Vec3::Y, 0.0)];
+        let state = step_movement(
+            &controller,
+            running(&controller, 8.0),
+            &crouch_forward(),
+            SHAPE,
+            &planes(&floor),
+            DT,
+        );
+        assert_eq!(state.move_mode, MoveMode::Ground);
+    }
+
+    #[test]
+    fn slide_boost_has_cooldown() {
+        let controller = slider();
+        let floor = [(Vec3::Y, 0.0)];
+        let mut inputs = vec![crouch_forward(); 3];
+        inputs[1].crouch = false;
+        let states = run_inputs(
+            &controller,
+            running(&controller, 14.0),
+            &planes(&floor),
+            &inputs,
+        );
+        assert_eq!(states[1].move_mode, MoveMode::Ground);
+        assert_eq!(states[2].move_mode, MoveMode::Slide);
+        // Only friction, no second boost
+        assert!(lateral_speed(&states[2]) < lateral_speed(&states[1]));
+    }
+
+    #[test]
+    fn slide_uses_slide_friction() {
+        let controller = FpsController {
+            slide_boost: 0.0,
+            ..slider()
+        };
+        let floor = [(Vec3::Y, 0.0)];
+        let state = simulate(
+            &controller,
+            running(&controller, 12.0),
+            &crouch_forward(),
+            &planes(&floor),
+            16,
+        );
+        assert_eq!(state.move_mode, MoveMode::Slide);
+        // Speed decays with the low slide friction instead of stopping quickly
+        let expected = 12.0 * (1.0 - controller.slide_friction * DT).powi(16);
+        assert!(
+            (lateral_speed(&state) - expected).abs() < 0.1,
+            "slid at {} instead of {expected}",
+            lateral_speed(&state)
+        );
+    }
+
+    #[test]
+    fn slide_accelerates_down_slope() {
+        let controller = FpsController {
+            slide_boost: 0.0,
+            slide_friction: 0.0,
+            ..slider()
+        };
+        // Slope going down toward -Z
+        let normal = Vec3::new(0.0, 1.0, -0.3).normalize();
+        let slope = [(normal, 0.0)];
+        let mut state = running(&controller, 11.0);
+        state.translation.y = (normal.y * state.height * 0.5 + normal.z.abs() * 0.5) / normal.y;
+        state.velocity = Vec3::NEG_Z.reject_from(normal).normalize() * 11.0;
+        let state = simulate(&controller, state, &crouch_forward(), &planes(&slope), 32);
+        assert_eq!(state.move_mode, MoveMode::Slide);
+        // Gravity along the slope over half a second
+        let gain = controller.gravity * normal.z.abs() * 0.5;
+        assert!(
+            state.velocity.length() > 11.0 + gain * 0.9,
+            "slid at {}",
+            state.velocity.length()
+        );
+    }
+
+    #[test]
+    fn slide_ends_below_end_speed() {
+        let controller = FpsController {
+            slide_boost: 0.0,
+            slide_friction: 10.0,
+            ..slider()
+        };
+        let floor = [(Vec3::Y, 0.0)];
+        let states = run_inputs(
+            &controller,
+            running(&controller, 12.0),
+            &planes(&floor),
+            &vec![crouch_forward(); 32],
+        );
+        let end = states
+            .iter()
+            .position(|state| state.move_mode != MoveMode::Slide)
+            .expect("slide never ended");
+        // The slide checks the speed from before this tick's friction
+        assert!(end > 1);
+        assert!(lateral_speed(&states[end - 2]) >= controller.slide_end_speed);
+        assert!(lateral_speed(&states[end - 1]) < controller.slide_end_speed);
+    }
+
+    #[test]
+    fn slide_ends_when_crouch_released() {
+        let controller = slider();
+        let floor = [(Vec3::Y, 0.0)];
+        let mut inputs = vec![crouch_forward(); 8];
+        inputs[7].crouch = false;
+        let states = run_inputs(
+            &controller,
+            running(&controller, 12.0),
+            &planes(&floor),
+            &inputs,
+        );
+        assert_eq!(states[6].move_mode, MoveMode::Slide);
+        assert_eq!(states[7].move_mode, MoveMode::Ground);
+    }
+}
@@ -0,0 +1,695 @@
+use std::{collections::BTreeMap, f32::consts::*};
+
+use bevy::{
+    input::mouse::{MouseMotion, MouseWheel},
+    prelude::*,
+};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::controller_core::*;
+
+/// Selects which local input device feeds a controller in [`fps_controller_input`].
+/// Controllers without this component are fed by the keyboard and mouse.
+#[derive(Component, Reflect, Clone, Copy, Debug, Default, PartialEq)]
+#[reflect(Component, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    reflect(Serialize, Deserialize)
+)]
+pub enum FpsInputDevice {
+    #[default]
+    KeyboardMouse,
+    /// Only the keys of the input map, so several local players can share one keyboard
+    Keyboard,
+    /// The gamepad entity, tuned by [`FpsGamepadSettings`]
+    Gamepad(Entity),
+    /// The input is written by other systems, for example for bots or networked players
+    None,
+}
+
+/// Analog tuning for controllers fed by [`FpsInputDevice::Gamepad`], the defaults are used if missing.
+#[derive(Component, Reflect, Clone, Debug, PartialEq)]
+#[reflect(Component, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    reflect(Serialize, Deserialize)
+)]
+pub struct FpsGamepadSettings {
+    /// Deflection of the movement stick below this is ignored
+    pub move_deadzone: f32,
+    /// Deflection of the look stick below this is ignored
+    pub look_deadzone: f32,
+    /// Exponent applied to the look stick deflection, values above one give finer aim near the center
+    pub look_response_curve: f32,
+    /// Turn rate in radians per second at full look stick deflection
+    pub look_sensitivity: f32,
+}
+
+impl Default for FpsGamepadSettings {
+    fn default() -> Self {
+        Self {
+            move_deadzone: 0.15,
+            look_deadzone: 0.15,
+            look_response_curve: 2.0,
+            look_sensitivity: 4.0,
+        }
+    }
+}
+
+/// Look tuning shared by mouse and gamepad look, the defaults are used if missing.
+#[derive(Component, Reflect, Clone, Debug, PartialEq)]
+#[reflect(Component, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    reflect(Serialize, Deserialize)
+)]
+pub struct FpsLookSettings {
+    /// Radians turned per pixel of mouse motion
+    pub mouse_sensitivity: f32,
+    /// Horizontal and vertical multipliers on top of the mouse or gamepad sensitivity
+    pub sensitivity: Vec2,
+    pub invert_y: bool,
+    /// Lowest pitch in radians, negative is looking down
+    pub min_pitch: f32,
+    /// Highest pitch in radians
+    pub max_pitch: f32,
+    /// Keeps the yaw within a range around a reference heading, unlimited if `None`
+    pub yaw_limits: Option<YawLimits>,
+    /// Vertical FOV in radians of the unzoomed camera. When set, look speed is scaled by
+    /// the ratio of the [`RenderPlayer`] camera's current perspective FOV to this
+    pub base_fov: Option<f32>,
+    /// Extra multiplier while the camera FOV is narrower than `base_fov`, like scoped sensitivity
+    pub zoom_sensitivity: f32,
+}
+
+impl Default for FpsLookSettings {
+    fn default() -> Self {
+        Self {
+            mouse_sensitivity: 0.001,
+            sensitivity: Vec2::ONE,
+            invert_y: false,
+            min_pitch: -FRAC_PI_2 + ANGLE_EPSILON,
+            max_pitch: FRAC_PI_2 - ANGLE_EPSILON,
+            yaw_limits: None,
+            base_fov: None,
+            zoom_sensitivity: 1.0,
+        }
+    }
+}
+
+/// Whether [`fps_controller_input`] gathers input for the controller on the same entity,
+/// turn it off while a menu has the cursor. Controllers without it are enabled.
+#[derive(Component, Reflect, Clone, Copy, Debug, PartialEq)]
+#[reflect(Component, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    reflect(Serialize, Deserialize)
+)]
+pub struct FpsInputEnabled(pub bool);
+
+impl Default for FpsInputEnabled {
+    fn default() -> Self {
+        Self(true)
+    }
+}
+
+/// Yaw range in radians relative to `reference`, for turrets, vehicle seats or constrained cutscenes.
+#[derive(Reflect, Clone, Copy, Debug, Default, PartialEq)]
+#[reflect(Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    reflect(Serialize, Deserialize)
+)]
+pub struct YawLimits {
+    /// Heading the limits are measured from
+    pub reference: f32,
+    /// Furthest turn to the right, usually negative
+    pub min: f32,
+    /// Furthest turn to the left
+    pub max: f32,
+}
+
+/// Whether an action is active while its input is held, or is switched on and off by pressing it.
+#[derive(Reflect, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    reflect(Serialize, Deserialize)
+)]
+pub enum ButtonMode {
+    #[default]
+    Hold,
+    Toggle,
+}
+
+/// Actions in [`ButtonMode::Toggle`] that are currently switched on.
+#[derive(Component, Reflect, Clone, Copy, Debug, Default, PartialEq)]
+#[reflect(Component, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    reflect(Serialize, Deserialize)
+)]
+pub struct FpsInputToggles {
+    pub crouch: bool,
+    pub sprint: bool,
+    pub walk: bool,
+}
+
+/// Actions of the controller that input sources can be bound to.
+#[derive(Reflect, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    reflect(Serialize, Deserialize)
+)]
+pub enum FpsAction {
+    MoveForward,
+    MoveBack,
+    MoveLeft,
+    MoveRight,
+    /// Only used while flying
+    MoveUp,
+    /// Only used while flying
+    MoveDown,
+    Sprint,
+    /// Slow movement modifier
+    Walk,
+    Jump,
+    Fly,
+    Crouch,
+}
+
+/// A digital input that can be bound to an [`FpsAction`].
+///
+/// The mouse wheel is an impulse, each scroll triggers the action for the next fixed tick.
+/// This allows jumping with the scroll wheel, which bunny hopping in the Source engine relies on.
+#[derive(Reflect, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    reflect(Serialize, Deserialize)
+)]
+pub enum FpsInputSource {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    MouseWheelUp,
+    MouseWheelDown,
+    Gamepad(GamepadButton),
+}
+
+/// Maps each action to the input sources that trigger it, any of them being pressed triggers the action.
+///
+/// Insert it on a logical player for per-player bindings, otherwise [`DefaultFpsInputMap`] is used.
+/// Keys and mouse buttons are read for [`FpsInputDevice::KeyboardMouse`] and [`FpsInputDevice::Keyboard`],
+/// gamepad buttons for [`FpsInputDevice::Gamepad`], so one map can hold bindings for all devices.
+/// With the `serde` feature the map can be saved to and loaded from a settings file.
+#[derive(Component, Reflect, Clone, Debug, PartialEq)]
+#[reflect(Component, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    reflect(Serialize, Deserialize)
+)]
+pub struct FpsInputMap {
+    pub bindings: BTreeMap<FpsAction, Vec<FpsInputSource>>,
+}
+
+impl Default for FpsInputMap {
+    fn default() -> Self {
+        use FpsAction::*;
+        use FpsInputSource::*;
+
+        Self::empty()
+            .with(MoveForward, Key(KeyCode::KeyW))
+            .with(MoveBack, Key(KeyCode::KeyS))
+            .with(MoveLeft, Key(KeyCode::KeyA))
+            .with(MoveRight, Key(KeyCode::KeyD))
+            .with(MoveUp, Key(KeyCode::KeyQ))
+            .with(MoveUp, Gamepad(GamepadButton::RightTrigger))
+            .with(MoveDown, Key(KeyCode::KeyE))
+            .with(MoveDown, Gamepad(GamepadButton::LeftTrigger))
+            .with(Sprint, Key(KeyCode::ShiftLeft))
+            .with(Sprint, Gamepad(GamepadButton::LeftThumb))
+            .with(Walk, Key(KeyCode::AltLeft))
+            .with(Jump, Key(KeyCode::Space))
+            .with(Jump, Gamepad(GamepadButton::South))
+            .with(Fly, Key(KeyCode::KeyF))
+            .with(Fly, Gamepad(GamepadButton::Select))
+            .with(Crouch, Key(KeyCode::ControlLeft))
+            .with(Crouch, Gamepad(GamepadButton::East))
+    }
+}
+
+impl FpsInputMap {
+    /// Returns a map without any bindings.
+    pub fn empty() -> Self {
+        Self {
+            bindings: BTreeMap::new(),
+        }
+    }
+
+    /// Adds a binding, for chaining when building a map.
+    pub fn with(mut self, action: FpsAction, source: FpsInputSource) -> Self {
+        self.bind(action, source);
+        self
+    }
+
+    /// Adds a source to the action, keeping the existing ones.
+    pub fn bind(&mut self, action: FpsAction, source: FpsInputSource) {
+        let sources = self.bindings.entry(action).or_default();
+        if !sources.contains(&source) {
+            sources.push(source);
+        }
+    }
+
+    /// Removes a source from the action.
+    pub fn unbind(&mut self, action: FpsAction, source: FpsInputSource) {
+        if let Some(sources) = self.bindings.get_mut(&action) {
+            sources.retain(|bound| *bound != source);
+        }
+    }
+
+    /// Removes all sources of the action.
+    pub fn clear(&mut self, action: FpsAction) {
+        self.bindings.remove(&action);
+    }
+
+    pub fn sources(&self, action: FpsAction) -> &[FpsInputSource] {
+        self.bindings.get(&action).map_or(&[], Vec::as_slice)
+    }
+}
+
+/// Bindings of controllers that do not have their own [`FpsInputMap`].
+#[derive(Resource, Reflect, Clone, Debug, Default, PartialEq)]
+#[reflect(Resource, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    reflect(Serialize, Deserialize)
+)]
+pub struct DefaultFpsInputMap(pub FpsInputMap);
+
+/// Digital input state of the devices feeding one controller.
+struct InputDevices<'a> {
+    keys: Option<&'a ButtonInput<KeyCode>>,
+    mouse_buttons: Option<&'a ButtonInput<MouseButton>>,
+    /// Whether the wheel scrolled up and down this frame
+    mouse_wheel: Option<(bool, bool)>,
+    gamepad: Option<&'a Gamepad>,
+}
+
+impl InputDevices<'_> {
+    fn pressed(&self, source: FpsInputSource) -> bool {
+        match source {
+            FpsInputSource::Key(key) => self.keys.is_some_and(|keys| keys.pressed(key)),
+            FpsInputSource::Mouse(button) => self
+                .mouse_buttons
+                .is_some_and(|buttons| buttons.pressed(button)),
+            // Scrolling has no held state, so it counts as a press for the frame it happened in
+            FpsInputSource::MouseWheelUp => self.mouse_wheel.is_some_and(|(up, _)| up),
+            FpsInputSource::MouseWheelDown => self.mouse_wheel.is_some_and(|(_, down)| down),
+            FpsInputSource::Gamepad(button) => {
+                self.gamepad.is_some_and(|gamepad| gamepad.pressed(button))
+            }
+        }
+    }
+
+    fn just_pressed(&self, source: FpsInputSource) -> bool {
+        match source {
+            FpsInputSource::Key(key) => self.keys.is_some_and(|keys| keys.just_pressed(key)),
+            FpsInputSource::Mouse(button) => self
+                .mouse_buttons
+                .is_some_and(|buttons| buttons.just_pressed(button)),
+            FpsInputSource::MouseWheelUp | FpsInputSource::MouseWheelDown => self.pressed(source),
+            FpsInputSource::Gamepad(button) => self
+                .gamepad
+                .is_some_and(|gamepad| gamepad.just_pressed(button)),
+        }
+    }
+
+    fn action_pressed(&self, map: &FpsInputMap, action: FpsAction) -> bool {
+        map.sources(action)
+            .iter()
+            .any(|source| self.pressed(*source))
+    }
+
+    fn action_just_pressed(&self, map: &FpsInputMap, action: FpsAction) -> bool {
+        map.sources(action)
+            .iter()
+            .any(|source| self.just_pressed(*source))
+    }
+
+    /// Returns whether the action is active, flipping the toggle on a press in toggle mode.
+    fn action_active(
+        &self,
+        map: &FpsInputMap,
+        action: FpsAction,
+        mode: ButtonMode,
+        toggled: &mut bool,
+    ) -> bool {
+        match mode {
+            ButtonMode::Hold => self.action_pressed(map, action),
+            ButtonMode::Toggle => {
+                if self.action_just_pressed(map, action) {
+                    *toggled = !*toggled;
+                }
+                *toggled
+            }
+        }
+    }
+
+    fn action_axis(&self, map: &FpsInputMap, positive: FpsAction, negative: FpsAction) -> f32 {
+        let value = |action| {
+            if self.action_pressed(map, action) {
+                1.0
+            } else {
+                0.0
+            }
+        };
+        value(positive) - value(negative)
+    }
+}
+
+// Used as padding by camera pitching (up/down) to avoid spooky math problems
+const ANGLE_EPSILON: f32 = 0.001953125;
+
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+pub fn fps_controller_input(
+    time: Res<Time>,
+    key_input: Res<ButtonInput<KeyCode>>,
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    mut mouse_events: MessageReader<MouseMotion>,
+    mut mouse_wheel_events: MessageReader<MouseWheel>,
+    default_input_map: Res<DefaultFpsInputMap>,
+    gamepads: Query<&Gamepad>,
+    cameras: Query<(&RenderPlayer, &Projection)>,
+    mut query: Query<(
+        Entity,
+        &FpsController,
+        &mut FpsControllerInput,
+        &mut FpsInputToggles,
+        Option<&FpsInputMap>,
+        Option<&FpsInputDevice>,
+        Option<&FpsGamepadSettings>,
+        Option<&FpsLookSettings>,
+        Option<&FpsInputEnabled>,
+    )>,
+) {
+    // Read once up front, every controller fed by the mouse sees the same motion
+    let mouse_motion: Vec2 = mouse_events.read().map(|event| event.delta).sum();
+    // Input is only cleared after a fixed tick ran, so a scroll is held until the next tick sees it
+    let mouse_wheel = mouse_wheel_events
+        .read()
+        .fold((false, false), |(up, down), event| {
+            (up || event.y > 0.0, down || event.y < 0.0)
+        });
+
+    for (
+        entity,
+        controller,
+        mut input,
+        mut toggles,
+        input_map,
+        device,
+        gamepad_settings,
+        look_settings,
+        enabled,
+    ) in query.iter_mut()
+    {
+        if enabled.is_some_and(|enabled| !enabled.0) {
+            continue;
+        }
+        let input_map = input_map.unwrap_or(&default_input_map.0);
+        let look_settings = look_settings.cloned().unwrap_or_default();
+        let gamepad_settings = gamepad_settings.cloned().unwrap_or_default();
+        let look_scale = look_settings.base_fov.map_or(1.0, |base_fov| {
+            let fov = cameras
+                .iter()
+                .find_map(|(render_player, projection)| match projection {
+                    Projection::Perspective(perspective)
+                        if render_player.logical_entity == entity =>
+                    {
+                        Some(perspective.fov)
+                    }
+                    _ => None,
+                });
+            fov_look_scale(
+                fov.unwrap_or(base_fov),
+                base_fov,
+                look_settings.zoom_sensitivity,
+            )
+        });
+
+        let devices = match device.copied().unwrap_or_default() {
+            FpsInputDevice::KeyboardMouse => {
+                add_look(
+                    &mut input,
+                    mouse_motion * look_settings.mouse_sensitivity * look_scale,
+                    &look_settings,
+                );
+                InputDevices {
+                    keys: Some(&key_input),
+                    mouse_buttons: Some(&mouse_input),
+                    mouse_wheel: Some(mouse_wheel),
+                    gamepad: None,
+                }
+            }
+            FpsInputDevice::Keyboard => InputDevices {
+                keys: Some(&key_input),
+                mouse_buttons: None,
+                mouse_wheel: None,
+                gamepad: None,
+            },
+            FpsInputDevice::Gamepad(entity) => {
+                // The gamepad may have been disconnected
+                let Ok(gamepad) = gamepads.get(entity) else {
+                    continue;
+                };
+                let look = apply_deadzone(
+                    gamepad.right_stick(),
+                    gamepad_settings.look_deadzone,
+                    gamepad_settings.look_response_curve,
+                );
+                // Stick up is positive, unlike mouse motion
+                add_look(
+                    &mut input,
+                    Vec2::new(look.x, -look.y)
+                        * gamepad_settings.look_sensitivity
+                        * look_scale
+                        * time.delta_secs(),
+                    &look_settings,
+                );
+                InputDevices {
+                    keys: None,
+                    mouse_buttons: None,
+                    mouse_wheel: None,
+                    gamepad: Some(gamepad),
+                }
+            }
+            FpsInputDevice::None => continue,
+        };
+
+        input.movement = Vec3::new(
+            devices.action_axis(input_map, FpsAction::MoveRight, FpsAction::MoveLeft),
+            devices.action_axis(input_map, FpsAction::MoveUp, FpsAction::MoveDown),
+            devices.action_axis(input_map, FpsAction::MoveForward, FpsAction::MoveBack),
+        );
+        if let Some(gamepad) = devices.gamepad {
+            let stick = apply_deadzone(gamepad.left_stick(), gamepad_settings.move_deadzone, 1.0);
+            input.movement.x = (input.movement.x + stick.x).clamp(-1.0, 1.0);
+            input.movement.z = (input.movement.z + stick.y).clamp(-1.0, 1.0);
+        }
+        let toggles = &mut *toggles;
+        input.sprint |= devices.action_active(
+            input_map,
+            FpsAction::Sprint,
+            controller.sprint_mode,
+            &mut toggles.sprint,
+        );
+        input.walk |= devices.action_active(
+            input_map,
+            FpsAction::Walk,
+            controller.walk_mode,
+            &mut toggles.walk,
+        );
+        input.jump |= devices.action_pressed(input_map, FpsAction::Jump);
+        input.fly |= devices.action_just_pressed(input_map, FpsAction::Fly);
+        input.crouch |= devices.action_active(
+            input_map,
+            FpsAction::Crouch,
+            controller.crouch_mode,
+            &mut toggles.crouch,
+        );
+    }
+}
+
+/// Ignores deflection inside the deadzone and rescales the rest to start from zero,
+/// then shapes the magnitude with the response curve exponent.
+fn apply_deadzone(stick: Vec2, deadzone: f32, response_curve: f32) -> Vec2 {
+    let magnitude = stick.length();
+    if magnitude <= deadzone {
+        return Vec2::ZERO;
+    }
+    let scaled = ((magnitude - deadzone) / (1.0 - deadzone)).min(1.0);
+    stick / magnitude * scaled.powf(response_curve)
+}
+
+/// Slows look down in proportion to how far the camera is zoomed in.
+fn fov_look_scale(fov: f32, base_fov: f32, zoom_sensitivity: f32) -> f32 {
+    if base_fov <= 0.0 {
+        return 1.0;
+    }
+    let scale = fov / base_fov;
+    if fov < base_fov {
+        scale * zoom_sensitivity
+    } else {
+        scale
+    }
+}
+
+/// Turns the look direction, the delta is in radians and follows mouse motion conventions.
+fn add_look(input: &mut FpsControllerInput, delta: Vec2, settings: &FpsLookSettings) {
+    let mut delta = delta * settings.sensitivity;
+    if settings.invert_y {
+        delta.y = -delta.y;
+    }
+    // Never let the configured bounds reach straight up or down
+    let min_pitch = settings.min_pitch.max(-FRAC_PI_2 + ANGLE_EPSILON);
+    let max_pitch = settings.max_pitch.min(FRAC_PI_2 - ANGLE_EPSILON);
+    input.pitch = (input.pitch - delta.y).clamp(min_pitch, max_pitch.max(min_pitch));
+    input.yaw -= delta.x;
+    if let Some(limits) = settings.yaw_limits {
+        // Offset from the reference wrapped to [-PI, PI), so the limits work across the seam
+        let offset = (input.yaw - limits.reference + PI).rem_euclid(TAU) - PI;
+        input.yaw = limits.reference + offset.clamp(limits.min, limits.max.max(limits.min));
+    }
+    if input.yaw.abs() > PI {
+        input.yaw = input.yaw.rem_euclid(TAU);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Difference between two angles, wrapped to [-PI, PI).
+    fn angle_between(a: f32, b: f32) -> f32 {
+        (a - b + PI).rem_euclid(TAU) - PI
+    }
+
+    fn yaw_limited(reference: f32, min: f32, max: f32) -> FpsLookSettings {
+        FpsLookSettings {
+            yaw_limits: Some(YawLimits {
+                reference,
+                min,
+                max,
+            }),
+            ..default()
+        }
+    }
+
+    #[test]
+    fn yaw_limits_work_across_the_seam() {
+        let settings = yaw_limited(PI - 0.1, -0.5, 0.5);
+        let mut input = FpsControllerInput {
+            yaw: PI - 0.1,
+            ..default()
+        };
+        // Turning left past PI stays within the limits instead of being clamped back
+        add_look(&mut input, Vec2::new(-0.3, 0.0), &settings);
+        assert!(angle_between(input.yaw, PI + 0.2).abs() < 1e-4);
+
+        add_look(&mut input, Vec2::new(-0.3, 0.0), &settings);
+        assert!(angle_between(input.yaw, PI + 0.4).abs() < 1e-4);
+
+        // Turning right from the wrapped side reaches the other limit
+        add_look(&mut input, Vec2::new(2.0, 0.0), &settings);
+        assert!(angle_between(input.yaw, PI - 0.6).abs() < 1e-4);
+    }
+
+    #[test]
+    fn inverted_limits_pin_the_look() {
+        let settings = FpsLookSettings {
+            min_pitch: 0.5,
+            max_pitch: 0.2,
+            ..yaw_limited(0.0, 0.5, -0.5)
+        };
+        let mut input = FpsControllerInput::default();
+        add_look(&mut input, Vec2::new(1.0, -1.0), &settings);
+        assert_eq!(input.pitch, 0.5);
+        assert!((input.yaw - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn pitch_never_reaches_straight_up() {
+        let settings = FpsLookSettings {
+            min_pitch: -PI,
+            max_pitch: PI,
+            ..default()
+        };
+        let mut input = FpsControllerInput::default();
+        add_look(&mut input, Vec2::new(0.0, -10.0), &settings);
+        assert!(input.pitch < FRAC_PI_2);
+    }
+
+    #[test]
+    fn invert_y_flips_pitch() {
+        let mut input = FpsControllerInput::default();
+        add_look(&mut input, Vec2::new(0.0, 0.1), &FpsLookSettings::default());
+        assert!((input.pitch + 0.1).abs() < 1e-6);
+
+        let inverted = FpsLookSettings {
+            invert_y: true,
+            ..default()
+        };
+        let mut input = FpsControllerInput::default();
+        add_look(&mut input, Vec2::new(0.0, 0.1), &inverted);
+        assert!((input.pitch - 0.1).abs() < 1e-6);
+    }
+
+    #[test]
+    fn deadzone_ignores_small_deflection() {
+        assert_eq!(apply_deadzone(Vec2::new(0.1, 0.05), 0.15, 1.0), Vec2::ZERO);
+        assert_eq!(apply_deadzone(Vec2::ZERO, 0.0, 1.0), Vec2::ZERO);
+    }
+
+    #[test]
+    fn deadzone_rescales_from_its_edge() {
+        let just_outside = apply_deadzone(Vec2::X * 0.16, 0.15, 1.0);
+        assert!(just_outside.length() < 0.02);
+        let full = apply_deadzone(Vec2::new(0.6, 0.8), 0.15, 1.0);
+        assert!((full - Vec2::new(0.6, 0.8)).length() < 1e-6);
+        // Pushing past the edge of the stick does not go faster
+        let beyond = apply_deadzone(Vec2::X * 1.2, 0.15, 1.0);
+        assert!((beyond.length() - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn response_curve_shapes_magnitude() {
+        let half = apply_deadzone(Vec2::Y * 0.5, 0.0, 2.0);
+        assert!((half - Vec2::Y * 0.25).length() < 1e-6);
+        let full = apply_deadzone(Vec2::Y, 0.0, 2.0);
+        assert!((full - Vec2::Y).length() < 1e-6);
+    }
+
+    #[test]
+    fn fov_scale_follows_zoom() {
+        assert_eq!(fov_look_scale(1.0, 1.0, 0.5), 1.0);
+        // Zoomed in the extra multiplier applies
+        assert!((fov_look_scale(0.5, 1.0, 0.5) - 0.25).abs() < 1e-6);
+        // Zoomed out only the ratio applies
+        assert!((fov_look_scale(1.5, 1.0, 0.5) - 1.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn fov_scale_ignores_invalid_base() {
+        assert_eq!(fov_look_scale(0.5, 0.0, 0.5), 1.0);
+    }
+}
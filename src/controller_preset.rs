@@ -0,0 +1,98 @@
+use bevy::{
+    asset::{io::Reader, AssetLoader, LoadContext},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::controller_core::*;
+
+/// Movement tunables of an [`FpsController`], loaded from `.movement.ron` files.
+/// The file is a plain `FpsController`, fields missing from it keep the values of [`FpsController::default`].
+#[derive(Asset, Reflect, Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct MovementPreset(pub FpsController);
+
+impl MovementPreset {
+    /// Captures the current tunables of a controller, useful for writing out a new preset.
+    pub fn from_controller(controller: &FpsController) -> Self {
+        Self(controller.clone())
+    }
+
+    /// Overwrites the tunables of the controller, keeping its collision mode, radius and button modes.
+    pub fn apply(&self, controller: &mut FpsController) {
+        *controller = FpsController {
+            collision_mode: controller.collision_mode,
+            radius: controller.radius,
+            crouch_mode: controller.crouch_mode,
+            sprint_mode: controller.sprint_mode,
+            walk_mode: controller.walk_mode,
+            ..self.0.clone()
+        };
+    }
+}
+
+/// Makes the [`FpsController`] on the same entity use the tunables of a [`MovementPreset`].
+/// They are applied again whenever the asset changes, so edits on disk show up live
+/// when Bevy's `file_watcher` feature is enabled.
+#[derive(Component, Reflect, Clone, Debug, Default)]
+#[reflect(Component, Default)]
+pub struct MovementPresetHandle(pub Handle<MovementPreset>);
+
+#[derive(Default, TypePath)]
+pub struct MovementPresetLoader;
+
+impl AssetLoader for MovementPresetLoader {
+    type Asset = MovementPreset;
+    type Settings = ();
+    type Error = BevyError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<MovementPreset, BevyError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["movement.ron"]
+    }
+}
+
+#[cfg(any(feature = "avian", feature = "rapier"))]
+pub(crate) fn add_preset_systems(app: &mut App) {
+    app.init_asset::<MovementPreset>()
+        .init_asset_loader::<MovementPresetLoader>()
+        .register_type::<MovementPresetHandle>()
+        .add_systems(
+            RunFixedMainLoop,
+            apply_movement_presets.in_set(RunFixedMainLoopSystems::BeforeFixedMainLoop),
+        );
+}
+
+/// Applies presets to controllers when the handle is set or the asset is loaded or modified.
+pub fn apply_movement_presets(
+    mut asset_events: MessageReader<AssetEvent<MovementPreset>>,
+    presets: Res<Assets<MovementPreset>>,
+    mut query: Query<(Ref<MovementPresetHandle>, &mut FpsController)>,
+) {
+    let changed_ids: Vec<_> = asset_events
+        .read()
+        .filter_map(|event| match *event {
+            AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id } => Some(id),
+            _ => None,
+        })
+        .collect();
+
+    for (handle, mut controller) in query.iter_mut() {
+        if !handle.is_changed() && !changed_ids.contains(&handle.0.id()) {
+            continue;
+        }
+        if let Some(preset) = presets.get(&handle.0) {
+            preset.apply(&mut controller);
+        }
+    }
+}
@@ -0,0 +1,354 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::controller_core::*;
+
+/// Inputs of every movement tick of a controller together with the state it started from.
+/// Replaying it on the same level reproduces the run, as long as physics is deterministic.
+/// With [`CollisionMode::Dynamic`] the velocity of the physics body is captured in the state.
+#[derive(Reflect, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[reflect(Default)]
+pub struct FpsInputRecording {
+    pub translation: Vec3,
+    pub state: FpsControllerState,
+    /// One entry per movement tick, serialized as runs of identical ticks
+    #[serde(with = "run_length")]
+    pub inputs: Vec<FpsControllerInput>,
+}
+
+impl FpsInputRecording {
+    /// Serializes the recording to a compact RON string, for example to attach to a bug report.
+    pub fn to_ron(&self) -> Result<String, BevyError> {
+        Ok(ron::to_string(self)?)
+    }
+
+    pub fn from_ron(text: &str) -> Result<Self, BevyError> {
+        Ok(ron::from_str(text)?)
+    }
+}
+
+/// Records the input of the controller on the same entity each movement tick.
+/// Remove the component or take its recording to stop.
+#[derive(Component, Reflect, Clone, Debug, Default)]
+#[reflect(Component, Default)]
+pub struct FpsInputRecorder {
+    pub recording: FpsInputRecording,
+}
+
+/// Feeds a recording into the controller on the same entity instead of its live input.
+/// The transform and controller state are reset to the start of the recording on the first tick.
+/// Use [`FpsInputDevice::None`](crate::controller::FpsInputDevice::None) so live look input does not mix in between ticks.
+#[derive(Component, Reflect, Clone, Debug, Default)]
+#[reflect(Component, Default)]
+pub struct FpsInputPlayback {
+    pub recording: FpsInputRecording,
+    /// Index of the next input to play
+    pub tick: usize,
+}
+
+impl FpsInputPlayback {
+    pub fn new(recording: FpsInputRecording) -> Self {
+        Self { recording, tick: 0 }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.tick >= self.recording.inputs.len()
+    }
+}
+
+#[cfg(any(feature = "avian", feature = "rapier"))]
+pub(crate) fn add_replay_systems<V: ControllerVelocity>(
+    app: &mut App,
+    movement_schedule: bevy::ecs::schedule::InternedScheduleLabel,
+) {
+    app.register_type::<FpsInputRecorder>()
+        .register_type::<FpsInputPlayback>()
+        .add_systems(
+            movement_schedule,
+            (fps_controller_playback::<V>, fps_controller_record::<V>)
+                .chain()
+                .before(FpsControllerSystems::Move),
+        );
+}
+
+/// Overwrites the controller input with the next recorded tick.
+#[allow(clippy::type_complexity)]
+pub fn fps_controller_playback<V: ControllerVelocity>(
+    mut query: Query<(
+        &mut FpsInputPlayback,
+        &FpsController,
+        &mut FpsControllerInput,
+        &mut FpsControllerState,
+        &mut Transform,
+        Option<&mut V>,
+    )>,
+) {
+    for (mut playback, controller, mut input, mut state, mut transform, velocity) in
+        query.iter_mut()
+    {
+        if playback.is_finished() {
+            continue;
+        }
+        if playback.tick == 0 {
+            transform.translation = playback.recording.translation;
+            *state = playback.recording.state;
+            if let Some(mut velocity) = velocity {
+                if controller.collision_mode == CollisionMode::Dynamic {
+                    velocity.set_linear_velocity(state.movement.velocity);
+                }
+            }
+        }
+        *input = playback.recording.inputs[playback.tick].clone();
+        state.pitch = input.pitch;
+        state.yaw = input.yaw;
+        playback.tick += 1;
+    }
+}
+
+/// Appends the input the controller is about to move with, capturing the start state on the first tick.
+#[allow(clippy::type_complexity)]
+pub fn fps_controller_record<V: ControllerVelocity>(
+    mut query: Query<(
+        &mut FpsInputRecorder,
+        &FpsController,
+        &FpsControllerInput,
+        &FpsControllerState,
+        &Transform,
+        Option<&V>,
+    )>,
+) {
+    for (mut recorder, controller, input, state, transform, velocity) in query.iter_mut() {
+        let recording = &mut recorder.recording;
+        if recording.inputs.is_empty() {
+            recording.translation = transform.translation;
+            recording.state = *state;
+            if let Some(velocity) = velocity {
+                if controller.collision_mode == CollisionMode::Dynamic {
+                    recording.state.movement.velocity = velocity.linear_velocity();
+                }
+            }
+        }
+        recording.inputs.push(input.clone());
+    }
+}
+
+/// Stores inputs as `(ticks, (buttons, pitch, yaw, movement))` runs, which keeps held keys
+/// and idle stretches to a single entry.
+mod run_length {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::*;
+
+    const FLY: u8 = 1 << 0;
+    const SPRINT: u8 = 1 << 1;
+    const WALK: u8 = 1 << 2;
+    const JUMP: u8 = 1 << 3;
+    const CROUCH: u8 = 1 << 4;
+
+    #[derive(PartialEq, Serialize, Deserialize)]
+    struct PackedInput(u8, f32, f32, Vec3);
+
+    impl From<&FpsControllerInput> for PackedInput {
+        fn from(input: &FpsControllerInput) -> Self {
+            let buttons = [
+                (input.fly, FLY),
+                (input.sprint, SPRINT),
+                (input.walk, WALK),
+                (input.jump, JUMP),
+                (input.crouch, CROUCH),
+            ]
+            .into_iter()
+            .filter(|(pressed, _)| *pressed)
+            .fold(0, |buttons, (_, bit)| buttons | bit);
+            Self(buttons, input.pitch, input.yaw, input.movement)
+        }
+    }
+
+    impl From<&PackedInput> for FpsControllerInput {
+        fn from(&PackedInput(buttons, pitch, yaw, movement): &PackedInput) -> Self {
+            Self {
+                fly: buttons & FLY != 0,
+                sprint: buttons & SPRINT != 0,
+                walk: buttons & WALK != 0,
+                jump: buttons & JUMP != 0,
+                crouch: buttons & CROUCH != 0,
+                pitch,
+                yaw,
+                movement,
+            }
+        }
+    }
+
+    pub fn serialize<S: Serializer>(
+        inputs: &[FpsControllerInput],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut runs: Vec<(u32, PackedInput)> = Vec::new();
+        for input in inputs.iter().map(PackedInput::from) {
+            match runs.last_mut() {
+                Some((ticks, last)) if *last == input => *ticks += 1,
+                _ => runs.push((1, input)),
+            }
+        }
+        runs.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<FpsControllerInput>, D::Error> {
+        let runs = Vec::<(u32, PackedInput)>::deserialize(deserializer)?;
+        Ok(runs
+            .iter()
+            .flat_map(|(ticks, input)| {
+                std::iter::repeat_n(FpsControllerInput::from(input), *ticks as usize)
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controller_core::tests::{planes, DT, SHAPE};
+
+    #[derive(Component)]
+    struct BodyVelocity(Vec3);
+
+    impl ControllerVelocity for BodyVelocity {
+        fn linear_velocity(&self) -> Vec3 {
+            self.0
+        }
+
+        fn set_linear_velocity(&mut self, velocity: Vec3) {
+            self.0 = velocity;
+        }
+    }
+
+    /// Moves on a flat floor, integrating the body velocity itself like a physics backend would.
+    fn move_on_floor(
+        mut query: Query<(
+            &FpsControllerInput,
+            &FpsController,
+            &mut FpsControllerState,
+            &mut Transform,
+            &mut BodyVelocity,
+        )>,
+    ) {
+        let floor = [(Vec3::Y, 0.0)];
+        let physics = planes(&floor);
+        for (input, controller, mut state, mut transform, mut velocity) in query.iter_mut() {
+            move_controller(
+                &physics,
+                input,
+                controller,
+                &mut state,
+                SHAPE,
+                &mut transform,
+                &mut velocity.0,
+                DT,
+            );
+            (transform.translation, velocity.0) =
+                move_and_slide(&physics, &SHAPE, transform.translation, velocity.0, 1.0, DT);
+        }
+    }
+
+    fn replay_app() -> App {
+        let mut app = App::new();
+        app.add_systems(
+            Update,
+            (
+                fps_controller_playback::<BodyVelocity>,
+                fps_controller_record::<BodyVelocity>,
+                move_on_floor,
+            )
+                .chain(),
+        );
+        app
+    }
+
+    /// Runs forward, turning slowly, with a jump and a sprint along the way.
+    fn run_inputs() -> Vec<FpsControllerInput> {
+        (0..64)
+            .map(|tick| FpsControllerInput {
+                movement: Vec3::NEG_Z,
+                jump: tick == 8,
+                sprint: tick >= 32,
+                yaw: tick as f32 * 0.01,
+                ..default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn recording_round_trips_through_ron() {
+        let recording = FpsInputRecording {
+            translation: Vec3::new(1.0, 2.0, 3.0),
+            state: FpsControllerState::new(&FpsController::default()),
+            inputs: run_inputs(),
+        };
+        let text = recording.to_ron().unwrap();
+        assert_eq!(FpsInputRecording::from_ron(&text).unwrap(), recording);
+    }
+
+    #[test]
+    fn repeated_inputs_are_stored_once() {
+        let forward = FpsControllerInput {
+            movement: Vec3::NEG_Z,
+            ..default()
+        };
+        let recording = FpsInputRecording {
+            inputs: vec![forward; 100],
+            ..default()
+        };
+        let text = recording.to_ron().unwrap();
+        assert!(text.contains("[(100,"), "{text}");
+        assert_eq!(FpsInputRecording::from_ron(&text).unwrap(), recording);
+    }
+
+    #[test]
+    fn playback_reproduces_dynamic_run() {
+        let controller = FpsController {
+            collision_mode: CollisionMode::Dynamic,
+            ..default()
+        };
+        let mut app = replay_app();
+        let entity = app
+            .world_mut()
+            .spawn((
+                controller.clone(),
+                FpsControllerInput::default(),
+                Transform::from_xyz(0.0, 1.5, 0.0),
+                BodyVelocity(Vec3::X * 4.0),
+                FpsInputRecorder::default(),
+            ))
+            .id();
+        let inputs = run_inputs();
+        for input in &inputs {
+            *app.world_mut()
+                .get_mut::<FpsControllerInput>(entity)
+                .unwrap() = input.clone();
+            app.update();
+        }
+        let world = app.world_mut();
+        let recorded = world.get::<FpsControllerState>(entity).unwrap().movement;
+        let recorder = world.entity_mut(entity).take::<FpsInputRecorder>().unwrap();
+        let recording = FpsInputRecording::from_ron(&recorder.recording.to_ron().unwrap()).unwrap();
+
+        // Start from somewhere else entirely, playback has to restore it all
+        world.entity_mut(entity).insert((
+            Transform::from_xyz(8.0, 4.0, 8.0),
+            BodyVelocity(Vec3::ZERO),
+            FpsControllerState::new(&controller),
+            FpsInputPlayback::new(recording),
+        ));
+        for _ in &inputs {
+            app.update();
+        }
+        let played = app
+            .world()
+            .get::<FpsControllerState>(entity)
+            .unwrap()
+            .movement;
+        assert_eq!(played, recorded);
+    }
+}
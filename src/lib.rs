@@ -1,11 +1,29 @@
 #[cfg(feature = "avian")]
 mod controller_avian;
+mod controller_core;
+mod controller_input;
+#[cfg(feature = "preset")]
+mod controller_preset;
 #[cfg(feature = "rapier")]
 mod controller_rapier;
+#[cfg(feature = "replay")]
+mod controller_replay;
 
 pub mod controller {
+    pub use crate::controller_core::*;
+    pub use crate::controller_input::*;
+    #[cfg(feature = "preset")]
+    pub use crate::controller_preset::*;
+    #[cfg(feature = "replay")]
+    pub use crate::controller_replay::*;
+
     #[cfg(feature = "avian")]
-    pub use crate::controller_avian::*;
+    pub mod avian {
+        pub use crate::controller_avian::*;
+    }
+
     #[cfg(feature = "rapier")]
-    pub use crate::controller_rapier::*;
+    pub mod rapier {
+        pub use crate::controller_rapier::*;
+    }
 }
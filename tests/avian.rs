@@ -0,0 +1,135 @@
+//! Headless checks of the Avian backend, stepping the app by one default fixed tick per update.
+
+use std::time::Duration;
+
+use avian3d::prelude::*;
+use bevy::{
+    asset::AssetPlugin, mesh::MeshPlugin, prelude::*, time::TimeUpdateStrategy,
+    transform::TransformPlugin,
+};
+use bevy_fps_controller::controller::{avian::*, *};
+
+const TICK: f64 = 1.0 / 64.0;
+
+fn app() -> App {
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        TransformPlugin,
+        AssetPlugin::default(),
+        MeshPlugin,
+        PhysicsPlugins::default(),
+        FpsControllerPlugin::default()
+            .without_input()
+            .without_render(),
+    ))
+    .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+        TICK,
+    )));
+    // Normally done by `App::run`, Avian registers some of its resources here
+    app.finish();
+    app.cleanup();
+    app
+}
+
+/// Spawns a player set up like the Avian example, standing at the given position.
+fn spawn_player(app: &mut App, translation: Vec3) -> Entity {
+    app.world_mut()
+        .spawn((
+            Collider::cylinder(0.5, 3.0),
+            Friction {
+                dynamic_coefficient: 0.0,
+                static_coefficient: 0.0,
+                combine_rule: CoefficientCombine::Min,
+            },
+            Restitution {
+                coefficient: 0.0,
+                combine_rule: CoefficientCombine::Min,
+            },
+            LinearVelocity::ZERO,
+            RigidBody::Dynamic,
+            LockedAxes::ROTATION_LOCKED,
+            Mass(1.0),
+            GravityScale(0.0),
+            Transform::from_translation(translation),
+            LogicalPlayer,
+            FpsControllerInput::default(),
+            FpsController::default(),
+        ))
+        .id()
+}
+
+fn spawn_box(app: &mut App, center: Vec3, size: Vec3) {
+    app.world_mut().spawn((
+        Collider::cuboid(size.x, size.y, size.z),
+        RigidBody::Static,
+        Transform::from_translation(center),
+    ));
+}
+
+/// Runs the given number of ticks, feeding the same input to the player every tick.
+fn run(app: &mut App, player: Entity, input: &FpsControllerInput, ticks: usize) -> Vec3 {
+    for _ in 0..ticks {
+        *app.world_mut()
+            .get_mut::<FpsControllerInput>(player)
+            .unwrap() = input.clone();
+        app.update();
+    }
+    app.world().get::<Transform>(player).unwrap().translation
+}
+
+#[test]
+fn steps_up_small_ledge() {
+    let mut app = app();
+    // Floor with a 0.2 high step starting two units in front of the player
+    spawn_box(
+        &mut app,
+        Vec3::new(0.0, -0.5, 0.0),
+        Vec3::new(100.0, 1.0, 100.0),
+    );
+    spawn_box(
+        &mut app,
+        Vec3::new(0.0, 0.1, -22.0),
+        Vec3::new(4.0, 0.2, 40.0),
+    );
+    let player = spawn_player(&mut app, Vec3::Y * 1.5);
+    run(&mut app, player, &FpsControllerInput::default(), 32);
+
+    let forward = FpsControllerInput {
+        movement: Vec3::Z,
+        ..default()
+    };
+    let translation = run(&mut app, player, &forward, 128);
+
+    assert!(translation.z < -5.0, "blocked by the step at {translation}");
+    assert!(
+        (translation.y - 1.7).abs() < 0.1,
+        "not standing on the step at {translation}"
+    );
+}
+
+#[test]
+fn crouching_clings_to_ledge() {
+    let mut app = app();
+    // Platform ending two units in front of the player, with nothing below it
+    spawn_box(
+        &mut app,
+        Vec3::new(0.0, -0.5, 0.0),
+        Vec3::new(4.0, 1.0, 4.0),
+    );
+    let player = spawn_player(&mut app, Vec3::Y * 1.5);
+    run(&mut app, player, &FpsControllerInput::default(), 32);
+
+    let crouch_forward = FpsControllerInput {
+        movement: Vec3::Z,
+        crouch: true,
+        ..default()
+    };
+    let translation = run(&mut app, player, &crouch_forward, 128);
+
+    assert!(translation.y > 0.5, "fell off the ledge at {translation}");
+    assert!(
+        translation.z > -2.5,
+        "walked off the ledge at {translation}"
+    );
+}